@@ -10,6 +10,11 @@ var<uniform> sticky: i32;
 var base_color_texture: texture_2d<f32>;
 @group(1) @binding(3)
 var base_color_sampler: sampler;
+// 0 = always, 1 = never, 2 = contagious, 3 = timed
+@group(1) @binding(4)
+var<uniform> policy: i32;
+@group(1) @binding(5)
+var<uniform> remaining: f32;
 
 fn make_kernel(tex: texture_2d<f32>, s: sampler, coord: vec2<f32>) -> array<vec4<f32>, 9> {
     let w = 1.0 / 50.;
@@ -41,11 +46,25 @@ fn fragment(
     let sobel = sqrt((sobel_edge_h * sobel_edge_h) + (sobel_edge_v * sobel_edge_v));
     // NOTE: This has to be outside of the if because of LOD: https://github.com/gfx-rs/wgpu-rs/issues/912
     let col = textureSample(base_color_texture, base_color_sampler, uv);
-    if sticky == 1 && length(sobel.a) > 2.5 {
-        return material.color;
-    } else {
+    let edge = length(sobel.a) > 2.5;
+    if !edge {
+        return col;
+    }
+    if policy == 1 {
+        // Slippery items get an icy outline
+        return vec4(0.6, 0.85, 1.0, 1.0);
+    }
+    if sticky != 1 {
         return col;
     }
+    if policy == 0 {
+        return vec4(1.0, 0.2, 0.8, 1.0);
+    } else if policy == 3 {
+        // Glue dries out as the time runs out
+        return mix(col, material.color, remaining);
+    } else {
+        return material.color;
+    }
     // return vec4(col.rgb * sobel.rgb, 1.);//vec4(1.0 - sobel.rgb, 1.0);
     // let col = textureSample(base_color_texture, base_color_sampler, uv);
     // if (distance(uv, vec2(0.5, 0.5)) > 0.5) {
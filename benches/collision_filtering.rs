@@ -0,0 +1,161 @@
+//! Measures the contact filtering of the game against the hierarchy walks it replaced.
+//!
+//! The game side uses the real [`Hooks`] and [`CollisionLayer`] groups. The walking hook is the
+//! one the game used before collision layers and is only kept here to compare against.
+//! Run with `cargo bench --bench collision_filtering`.
+
+use std::time::{Duration, Instant};
+
+use bevy::{ecs::query::WorldQuery, prelude::*};
+use bevy_rapier2d::prelude::*;
+use tricky_throws::game::{
+    cluster::ClusterId,
+    physics::{CollisionLayer, Hooks, PhysicsData, PhysicsScale},
+};
+
+const BODIES: usize = 400;
+const STEPS: u32 = 300;
+
+/// Marker the game used for queued and held items before they got their own layer
+#[derive(Component)]
+struct IgnoreCollisions;
+
+struct WalkingHooks;
+
+#[derive(WorldQuery)]
+struct WalkingData<'a> {
+    parent: Option<&'a Parent>,
+    cluster: Option<&'a ClusterId>,
+    ignore_collisions: Option<&'a IgnoreCollisions>,
+}
+
+fn walk<T, F>(query: &Query<WalkingData>, e: Entity, get: F) -> Option<T>
+where
+    F: Fn(&WalkingDataItem) -> Option<T> + Copy,
+{
+    let data = query.get(e).ok()?;
+    get(&data).or_else(|| walk(query, data.parent?.get(), get))
+}
+
+fn root(query: &Query<WalkingData>, mut e: Entity) -> Entity {
+    while let Some(p) = query.get(e).ok().and_then(|d| d.parent) {
+        e = p.get();
+    }
+    e
+}
+
+impl<'a> PhysicsHooksWithQuery<WalkingData<'a>> for WalkingHooks {
+    fn filter_contact_pair(
+        &self,
+        context: PairFilterContextView,
+        query: &Query<WalkingData<'a>>,
+    ) -> Option<SolverFlags> {
+        let (a, b) = (context.collider1(), context.collider2());
+        let ignored = |e| walk(query, e, |d| d.ignore_collisions.map(|_| ())).is_some();
+        if ignored(a) || ignored(b) {
+            return None;
+        }
+        let cluster = |e| query.get(root(query, e)).ok().and_then(|d| d.cluster);
+        match (cluster(a), cluster(b)) {
+            (Some(c1), Some(c2)) if c1 == c2 => None,
+            _ => Some(SolverFlags::COMPUTE_IMPULSES),
+        }
+    }
+}
+
+/// Pile of bodies with two colliders each, where some are held
+fn spawn_bodies(world: &mut World, layers: bool) {
+    for (x, hx, hy) in [(0., 400., 20.), (-420., 20., 1000.), (420., 20., 1000.)] {
+        let mut wall = world.spawn((
+            Collider::cuboid(hx, hy),
+            TransformBundle::from(Transform::from_xyz(x, if hx > hy { -20. } else { 0. }, 0.)),
+        ));
+        if layers {
+            wall.insert(CollisionLayer::World.groups());
+        }
+    }
+
+    for i in 0..BODIES {
+        let layer = match i % 10 {
+            0 | 5 => CollisionLayer::Held,
+            _ => CollisionLayer::Item,
+        };
+        let pos = Vec2::new((i % 20) as f32 * 35. - 330., (i / 20) as f32 * 35. + 20.);
+        let mut body = world.spawn((
+            RigidBody::Dynamic,
+            TransformBundle::from(Transform::from_xyz(pos.x, pos.y, 0.)),
+            // Neighbours share clusters like glued items do
+            ClusterId(i as u32 / 3),
+        ));
+        if layer == CollisionLayer::Held && !layers {
+            body.insert(IgnoreCollisions);
+        }
+        body.with_children(|children| {
+            for offset in [-8., 8.] {
+                let mut collider = children.spawn((
+                    Collider::ball(10.),
+                    ActiveHooks::FILTER_CONTACT_PAIRS,
+                    TransformBundle::from(Transform::from_xyz(offset, 0., 0.)),
+                ));
+                if layers {
+                    collider.insert(layer.groups());
+                }
+            }
+        });
+    }
+}
+
+fn app<Data: 'static + WorldQuery + Send + Sync>(
+    hooks: PhysicsHooksWithQueryResource<Data>,
+    layers: bool,
+) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Mesh>()
+        .add_asset::<Scene>()
+        .add_plugin(RapierPhysicsPlugin::<Data>::pixels_per_meter(
+            PhysicsScale::default().0,
+        ))
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1. / 60.,
+                substeps: 1,
+            },
+            ..default()
+        })
+        .insert_resource(hooks);
+    spawn_bodies(&mut app.world, layers);
+    app
+}
+
+fn run(name: &str, mut app: App) -> Duration {
+    // Let the bodies settle into a pile before measuring
+    for _ in 0..60 {
+        app.update();
+    }
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        app.update();
+    }
+    let elapsed = start.elapsed();
+    println!("{name}: {:?} per step", elapsed / STEPS);
+    elapsed
+}
+
+fn main() {
+    let walking = run(
+        "hierarchy walks",
+        app(PhysicsHooksWithQueryResource(Box::new(WalkingHooks)), false),
+    );
+    let layers = run(
+        "collision layers",
+        app::<PhysicsData<'static>>(PhysicsHooksWithQueryResource(Box::new(Hooks)), true),
+    );
+    println!(
+        "speedup with {BODIES} bodies: {:.2}x",
+        walking.as_secs_f64() / layers.as_secs_f64()
+    );
+}
@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    game::{
+        items::ItemAssets,
+        physics::{CollisionLayer, Hooks},
+        sculpture::{spawn_sculpture, Gallery, Sculpture},
+        shaders::StickyMaterial,
+        FLOOR,
+    },
+    utils::despawn_screen,
+};
+
+use super::GameState;
+
+const FREEZE_KEY: KeyCode = KeyCode::F;
+
+pub struct GalleryPlugin;
+
+impl Plugin for GalleryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Gallery).with_system(gallery_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::Gallery)
+                    .with_system(handle_gallery_input)
+                    .with_system(handle_sculpture_spawning.after(handle_gallery_input)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Gallery).with_system(despawn_screen::<OnGallery>),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnGallery;
+
+/// Items of the shown sculpture
+#[derive(Component)]
+struct SculpturePiece;
+
+#[derive(Resource)]
+struct GalleryScene {
+    sculpture: Option<Handle<Sculpture>>,
+    spawned: bool,
+}
+
+fn gallery_setup(mut commands: Commands, asset_server: Res<AssetServer>, gallery: Res<Gallery>) {
+    commands.insert_resource(PhysicsHooksWithQueryResource(Box::new(Hooks)));
+    commands.insert_resource(GalleryScene {
+        sculpture: gallery
+            .path
+            .as_ref()
+            .map(|path| asset_server.load(path.as_str())),
+        spawned: false,
+    });
+
+    commands.spawn((
+        Collider::cuboid(1000.0, 25.0),
+        CollisionLayer::World,
+        CollisionLayer::World.groups(),
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::DARK_GRAY,
+                custom_size: Some(Vec2::new(2000.0, 50.0)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, FLOOR - 25.0, 0.0),
+            ..default()
+        },
+        OnGallery,
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "f: freeze\nesc: new game",
+            TextStyle {
+                font: asset_server.load("fonts/MajorMonoDisplay-Regular.ttf"),
+                font_size: 25.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(25.),
+                top: Val::Px(25.),
+                ..default()
+            },
+            ..default()
+        }),
+        OnGallery,
+    ));
+}
+
+fn handle_gallery_input(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut gallery: ResMut<Gallery>,
+    mut scene: ResMut<GalleryScene>,
+    mut game_state: ResMut<State<GameState>>,
+    pieces: Query<Entity, With<SculpturePiece>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        gallery.on_start = false;
+        game_state.set(GameState::Game).unwrap();
+    }
+    if keys.just_pressed(FREEZE_KEY) {
+        gallery.frozen = !gallery.frozen;
+        for piece in pieces.iter() {
+            commands.entity(piece).despawn_recursive();
+        }
+        scene.spawned = false;
+    }
+}
+
+/// Spawns the sculpture once it and the items it's made of are loaded
+fn handle_sculpture_spawning(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut custom_materials: ResMut<Assets<StickyMaterial>>,
+    items: ItemAssets,
+    sculptures: Res<Assets<Sculpture>>,
+    gallery: Res<Gallery>,
+    mut scene: ResMut<GalleryScene>,
+) {
+    if scene.spawned || !items.is_loaded() {
+        return;
+    }
+    if let Some(sculpture) = scene.sculpture.as_ref().and_then(|s| sculptures.get(s)) {
+        let pieces = spawn_sculpture(
+            &mut commands,
+            &asset_server,
+            &mut meshes,
+            &mut custom_materials,
+            &items,
+            sculpture,
+            Vec2::new(0., FLOOR),
+            gallery.frozen,
+        );
+        for piece in pieces {
+            commands.entity(piece).insert((SculpturePiece, OnGallery));
+        }
+        scene.spawned = true;
+    }
+}
@@ -1,14 +1,41 @@
 use self::{
-    items::{random_item, shoe},
-    physics::{handle_collisions, Hooks, StuckItems},
+    balloon::{handle_balloon_lift, handle_balloon_popping},
+    bomb::handle_bombs,
+    cluster::{handle_cluster_ids, handle_cluster_removals, handle_clusters, StuckItems},
+    debug::{handle_debug_overlay, handle_debug_toggle, DebugOverlay},
+    determinism::{handle_fixed_time, setup_determinism, Determinism, RunSeed},
+    dome::{handle_dome_deflections, spawn_dome},
+    fields::{handle_field_streaks, handle_fields, spawn_fields},
+    fracture::{handle_fractures, FractureRng},
+    items::{
+        handle_item_reloading, load_items, spawn_item, ItemAssets, ItemCatalog, ItemCatalogLoader,
+        ItemDefinition, ItemDefinitionLoader,
+    },
+    level::{load_level, CurrentLevel, Level, LevelLoader},
+    magnet::handle_magnets,
+    outline::{handle_outline_tracing, Outlines},
+    physics::{
+        handle_collision_layers, handle_collisions, handle_glue_strength, reset_external_forces,
+        CollisionLayer, Hooks,
+    },
+    platform::{handle_platforms, spawn_platforms},
+    randomizer::Randomizer,
+    replay::{handle_replay_end, handle_throw_input, setup_replay, ReplayMode, ThrowInput},
+    sculpture::{
+        handle_gallery_opening, handle_sculpture_export, Gallery, Sculpture, SculptureLoader,
+    },
     shaders::{handle_stickiness_effect, StickyMaterial, TilingMaterial},
+    spawning::{load_spawn_tables, SpawnContext, SpawnTable, SpawnTableLoader},
+    stickiness::handle_stickiness,
+    structure::handle_structure_scoring,
     throw::{
         generate_item, handle_disabling, handle_stored_items, handle_throwable_removals,
-        handle_throwing, Player, ThrowIndicator, Throwable,
+        handle_throwing, Player, Throwable,
     },
+    trajectory::{handle_trajectory_preview, setup_trajectory_preview},
 };
 use crate::{utils::despawn_screen, Music};
-use std::{collections::VecDeque, f32::consts::TAU, sync::Mutex, time::Duration};
+use std::{collections::VecDeque, f32::consts::TAU, time::Duration};
 
 use super::GameState;
 use bevy::{
@@ -16,48 +43,145 @@ use bevy::{
     prelude::*,
     render::texture::ImageSampler,
     sprite::MaterialMesh2dBundle,
-    utils::{HashMap, HashSet},
+    time::{Stopwatch, TimeSystem},
+    utils::HashSet,
 };
 use bevy_rapier2d::prelude::*;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use union_find::QuickFindUf;
 use wgpu::{AddressMode, SamplerBorderColor, SamplerDescriptor};
 
-mod collision_test;
-mod items;
+pub mod balloon;
+pub mod bomb;
+pub mod cluster;
+pub mod debug;
+pub mod determinism;
+pub mod dome;
+pub mod fields;
+pub mod fracture;
+#[cfg(test)]
+mod harness;
+pub mod items;
+pub mod level;
+pub mod magnet;
+mod outline;
 pub mod physics;
+pub mod platform;
+pub mod randomizer;
+pub mod replay;
+pub mod sculpture;
 pub mod shaders;
+pub mod spawning;
+pub mod stickiness;
+pub mod structure;
 mod throw;
+pub mod trajectory;
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_enter(GameState::Game)
-                .with_system(setup_graphics)
-                .with_system(setup_physics)
-                .with_system(setup_game),
-        )
+        app.add_asset::<ItemDefinition>()
+            .add_asset::<ItemCatalog>()
+            .init_asset_loader::<ItemDefinitionLoader>()
+            .init_asset_loader::<ItemCatalogLoader>()
+            .add_asset::<SpawnTable>()
+            .init_asset_loader::<SpawnTableLoader>()
+            .add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_asset::<Sculpture>()
+            .init_asset_loader::<SculptureLoader>()
+            .init_resource::<Gallery>()
+            .init_resource::<Outlines>()
+            .init_resource::<DebugOverlay>()
+            .init_resource::<Determinism>()
+            .init_resource::<ReplayMode>()
+            .init_resource::<ThrowInput>()
+            .add_startup_system(load_items)
+            .add_startup_system(load_spawn_tables)
+            .add_startup_system(load_level)
+            .add_startup_system(setup_determinism)
+            .add_system(handle_item_reloading)
+            .add_system(handle_outline_tracing)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Game)
+                    .with_system(setup_graphics)
+                    .with_system(setup_physics)
+                    .with_system(setup_game)
+                    .with_system(setup_replay)
+                    .with_system(setup_trajectory_preview),
+            );
+        add_gameplay_systems(app, SystemSet::on_update(GameState::Game))
+            .add_system_set(
+                SystemSet::on_update(GameState::Game)
+                    .with_system(modify_texture)
+                    .with_system(handle_field_streaks)
+                    .with_system(handle_stored_items)
+                    .with_system(handle_throw_input)
+                    .with_system(
+                        handle_throwing
+                            .after(handle_stored_items)
+                            .after(handle_throw_input),
+                    )
+                    .with_system(handle_trajectory_preview.after(handle_throwing))
+                    .with_system(handle_dome_deflections)
+                    .with_system(handle_item_dropping)
+                    .with_system(handle_death_timer)
+                    .with_system(handle_score_display)
+                    .with_system(handle_scoring_effect)
+                    .with_system(handle_lives_display)
+                    .with_system(handle_death)
+                    .with_system(handle_disabling)
+                    .with_system(handle_stickiness_effect)
+                    .with_system(customizing_sampler)
+                    .with_system(handle_sculpture_export)
+                    .with_system(handle_gallery_opening)
+                    .with_system(handle_debug_toggle)
+                    .with_system(handle_debug_overlay.after(handle_debug_toggle)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Game)
+                    .with_system(handle_replay_end)
+                    .with_system(despawn_screen::<OnGame>),
+            );
+    }
+}
+
+/// Systems that play the game out, in the set the game runs them in.
+///
+/// Test harness runs these too, so that tests go through the same schedule as the game.
+pub(crate) fn add_gameplay_systems(app: &mut App, set: SystemSet) -> &mut App {
+    app.add_system_to_stage(CoreStage::First, handle_fixed_time.after(TimeSystem))
         .add_system_set(
-            SystemSet::on_update(GameState::Game)
-                .with_system(modify_texture)
+            set.with_system(handle_game_clock)
+                .with_system(handle_platforms.after(handle_game_clock))
+                .with_system(handle_stickiness.before(handle_collisions))
                 .with_system(handle_collisions)
-                .with_system(handle_stored_items)
-                .with_system(handle_throwing.after(handle_stored_items))
-                .with_system(handle_item_dropping)
-                .with_system(handle_death_timer)
-                .with_system(handle_score_display)
-                .with_system(handle_scoring_effect)
-                .with_system(handle_lives_display)
-                .with_system(handle_death)
-                .with_system(handle_disabling)
-                .with_system(handle_stickiness_effect)
-                .with_system(customizing_sampler),
+                .with_system(handle_glue_strength)
+                .with_system(handle_fractures)
+                .with_system(
+                    handle_bombs
+                        .after(handle_collisions)
+                        .after(handle_fractures),
+                )
+                .with_system(reset_external_forces)
+                .with_system(handle_magnets.after(reset_external_forces))
+                .with_system(handle_fields.after(reset_external_forces))
+                .with_system(handle_balloon_lift)
+                .with_system(handle_balloon_popping)
+                .with_system(
+                    handle_cluster_ids
+                        .after(handle_collisions)
+                        .after(handle_glue_strength)
+                        .after(handle_fractures)
+                        .after(handle_bombs)
+                        .after(handle_balloon_popping),
+                )
+                .with_system(handle_clusters.after(handle_cluster_ids))
+                .with_system(handle_structure_scoring.after(handle_clusters)),
         )
         .add_system_to_stage(CoreStage::PostUpdate, handle_throwable_removals)
-        .add_system_set(SystemSet::on_exit(GameState::Game).with_system(despawn_screen::<OnGame>));
-    }
+        .add_system_to_stage(CoreStage::PostUpdate, handle_cluster_removals)
+        .add_system_to_stage(CoreStage::PostUpdate, handle_collision_layers)
 }
 
 fn handle_death(
@@ -179,11 +303,13 @@ pub struct Disabler;
 fn setup_game(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    audio_sinks: Res<Assets<AudioSink>>,
-    mut music: ResMut<Music>,
+    _audio_sinks: Res<Assets<AudioSink>>,
+    _music: ResMut<Music>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut custom_materials: ResMut<Assets<StickyMaterial>>,
-    audio: Res<Audio>,
+    _audio: Res<Audio>,
+    items: ItemAssets,
+    determinism: Res<Determinism>,
 ) {
     // if let Some(handle) = music.0.take() {
     //     audio_sinks.get(&handle).map(|sink| sink.stop());
@@ -215,23 +341,26 @@ fn setup_game(
                 RigidBody::Fixed,
                 Sensor,
                 Collider::ball(200.),
+                CollisionLayer::Sensor,
+                CollisionLayer::Sensor.groups(),
                 Disabler,
                 TransformBundle::from(Transform::from_xyz(0., 0., 0.)),
             ));
         });
 
-    commands.spawn((
-        StuckItems {
-            union_find: Mutex::new(QuickFindUf::from_iter(None)),
-            map: HashMap::new(),
-        },
-        OnGame,
-    ));
+    commands.spawn((StuckItems::default(), OnGame));
 
+    let table = |handle| items.spawn_table(handle).map(|t| t.randomizer);
+    let tables = items.tables();
+    let seed = determinism.game_seed();
+    info!("Game seed {seed}");
+    commands.insert_resource(RunSeed(seed));
+    let mut rng = SmallRng::seed_from_u64(seed);
     let mut cur = Current {
         current: None,
         next: VecDeque::default(),
-        rng: SmallRng::from_entropy(),
+        randomizer: table(&tables.queue).unwrap_or_default().build(rng.gen()),
+        rng: SmallRng::from_rng(&mut rng).unwrap(),
     };
     for _ in 0..3 {
         generate_item(
@@ -239,17 +368,19 @@ fn setup_game(
             &asset_server,
             &mut meshes,
             &mut custom_materials,
+            &items,
+            &SpawnContext::default(),
             &mut cur,
         );
     }
     commands.insert_resource(cur);
-    commands.insert_resource(ThrowIndicator {
-        timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-    });
+    commands.insert_resource(GameClock::default());
     commands.insert_resource(ItemDropTimer {
         timer: Timer::from_seconds(2., TimerMode::Repeating),
-        rng: SmallRng::from_entropy(),
+        randomizer: table(&tables.drops).unwrap_or_default().build(rng.gen()),
+        rng: SmallRng::from_rng(&mut rng).unwrap(),
     });
+    commands.insert_resource(FractureRng(SmallRng::from_rng(&mut rng).unwrap()));
 }
 
 #[derive(Clone, Debug, Component)]
@@ -276,24 +407,41 @@ fn setup_physics(
     mut materials: ResMut<Assets<TilingMaterial>>,
     mut custom_materials: ResMut<Assets<StickyMaterial>>,
     asset_server: Res<AssetServer>,
+    items: ItemAssets,
+    (current_level, levels): (Res<CurrentLevel>, Res<Assets<Level>>),
 ) {
     commands.insert_resource(PhysicsHooksWithQueryResource(Box::new(Hooks)));
 
-    // collision_test::test_collisions(commands);
+    if let Some(level) = levels.get(&current_level.0) {
+        spawn_fields(&mut commands, &level.fields);
+        spawn_platforms(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &asset_server,
+            &level.platforms,
+        );
+    }
 
     commands.spawn((
         Collider::cuboid(1000.0, 25.0),
+        CollisionLayer::World,
+        CollisionLayer::World.groups(),
         Destroyer,
-        TransformBundle::from(Transform::from_xyz(0.0, -800.0, 0.0)),
+        TransformBundle::from(Transform::from_xyz(0.0, FLOOR - 25.0, 0.0)),
         OnGame,
     ));
 
+    spawn_dome(&mut commands, SOURCE).insert(OnGame);
+
     let texture_handle = asset_server.load("bricks.png");
     let mesh = Mesh::from(shape::Quad::new(2. * Vec2::new(20.0, 575.0)));
 
     commands.spawn((
         Collider::cuboid(20.0, 700.0),
         Restitution::coefficient(4.),
+        CollisionLayer::World,
+        CollisionLayer::World.groups(),
         Wall,
         OnGame,
         MaterialMesh2dBundle {
@@ -311,6 +459,8 @@ fn setup_physics(
     commands.spawn((
         Collider::cuboid(20.0, 700.0),
         Restitution::coefficient(4.),
+        CollisionLayer::World,
+        CollisionLayer::World.groups(),
         Wall,
         OnGame,
         MaterialMesh2dBundle {
@@ -323,12 +473,18 @@ fn setup_physics(
     ));
 
     // TODO: Create AI that throws items
-    shoe(
+    let shoe = items
+        .by_name("shoe")
+        .expect("Item catalog should contain shoe");
+    spawn_item(
         &mut commands,
         &asset_server,
         &mut meshes,
         &mut custom_materials,
+        &items,
+        shoe,
         50.,
+        CollisionLayer::Item,
     )
     .insert((
         TransformBundle::from(Transform::from_xyz(ENEMY_SOURCE.x, ENEMY_SOURCE.y, 0.0)),
@@ -341,16 +497,26 @@ fn setup_physics(
     ));
 }
 
+/// Time spent in the current game
+#[derive(Resource, Default)]
+pub struct GameClock(pub Stopwatch);
+fn handle_game_clock(time: Res<Time>, mut clock: ResMut<GameClock>) {
+    clock.0.tick(time.delta());
+}
+
 #[derive(Resource)]
 pub struct Current {
     pub current: Option<Entity>,
     pub next: VecDeque<Entity>,
     pub rng: SmallRng,
+    pub randomizer: Box<dyn Randomizer>,
 }
 
 pub const STORAGE: Vec2 = Vec2::new(-900.0, -400.0);
 pub const SOURCE: Vec2 = Vec2::new(-600.0, -375.0);
 pub const ENEMY_SOURCE: Vec2 = Vec2::new(600.0, -375.0);
+/// Top of the destroyer at the bottom of the screen
+pub const FLOOR: f32 = -775.0;
 
 #[derive(Component)]
 pub struct DeathTimer(pub Timer);
@@ -370,6 +536,7 @@ fn handle_death_timer(
 pub struct ItemDropTimer {
     timer: Timer,
     rng: SmallRng,
+    randomizer: Box<dyn Randomizer>,
 }
 fn handle_item_dropping(
     mut commands: Commands,
@@ -378,6 +545,9 @@ fn handle_item_dropping(
     mut custom_materials: ResMut<Assets<StickyMaterial>>,
     time: Res<Time>,
     mut timer: ResMut<ItemDropTimer>,
+    items: ItemAssets,
+    players: Query<&Player>,
+    clock: Res<GameClock>,
 ) {
     let commands = &mut commands;
     if timer.timer.tick(time.delta()).just_finished() {
@@ -385,17 +555,32 @@ fn handle_item_dropping(
         let y = timer.rng.gen_range(0..=100);
         let transform = Transform::from_xyz(x as f32, 550. + y as f32, 5.);
         let angle = TAU / 8.;
-        random_item(
+        let context = SpawnContext {
+            score: players.single().score,
+            elapsed: clock.0.elapsed_secs(),
+        };
+        let timer = &mut *timer;
+        let (item, radius) = items.choose(
             &mut timer.rng,
+            &mut *timer.randomizer,
+            &items.tables().drops,
+            &context,
+        );
+        let definition = items.definition(item).unwrap();
+        spawn_item(
             commands,
             &asset_server,
             &mut meshes,
             &mut custom_materials,
+            &items,
+            item,
+            radius,
+            CollisionLayer::Item,
         )
         .insert((
             TransformBundle::from(transform),
             GravityScale(0.8),
-            Throwable::new(None, true),
+            Throwable::new(None, definition.stickiness.starts_sticky()),
             ExternalImpulse {
                 impulse: Vec2::ZERO,
                 torque_impulse: timer.rng.gen_range(-angle..=angle),
@@ -412,7 +597,7 @@ pub struct ScoringEffect {
 }
 fn handle_scoring_effect(mut scoring_text: Query<(&mut Text, &ScoringEffect, &DeathTimer)>) {
     for (mut text, effect, timer) in scoring_text.iter_mut() {
-        let mut style = &mut text.sections[0].style;
+        let style = &mut text.sections[0].style;
         style.font_size = effect.points as f32 + timer.0.percent() * 30.;
         if effect.multiplier > 1 {
             style.color = Color::PURPLE;
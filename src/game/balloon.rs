@@ -0,0 +1,179 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier2d::{prelude::*, rapier::prelude::CollisionEventFlags};
+use serde::Deserialize;
+
+use super::{
+    cluster::StuckItems,
+    physics::{body_of, despawn_item},
+    throw::{IgnoreCollisions, Player},
+    DeathTimer, Destroyer, OnGame,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct BalloonDefinition {
+    /// Mass the balloon can hold up
+    pub lift: f32,
+    /// Fastest the balloon can rise relative to gravity, also when lifting light clusters
+    pub max_rise: f32,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Balloon {
+    pub lift: f32,
+    pub max_rise: f32,
+}
+
+impl Balloon {
+    pub fn new(definition: &BalloonDefinition) -> Self {
+        Self {
+            lift: definition.lift,
+            max_rise: definition.max_rise,
+        }
+    }
+
+    /// Gravity scale of the balloon with nothing glued to it
+    pub fn gravity_scale(&self, mass: f32) -> f32 {
+        lift_gravity_scale(self.lift, mass, self.lift, mass, mass, self.max_rise)
+    }
+}
+
+/// Gravity scale that has the balloon carry its share of the cluster.
+///
+/// Totals are over the whole cluster and over the balloons in it.
+fn lift_gravity_scale(
+    lift: f32,
+    mass: f32,
+    total_lift: f32,
+    total_mass: f32,
+    balloon_mass: f32,
+    max_rise: f32,
+) -> f32 {
+    // Net force on the whole cluster is capped, and the balloons also carry the rest of the cluster
+    let net = (total_lift - total_mass).min(max_rise * total_mass);
+    let carried = net + total_mass - balloon_mass;
+    // Balloons share the work by their lift
+    -carried * (lift / total_lift) / mass
+}
+
+/// Pops balloons
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Sharp;
+
+/// Sets gravity scale of balloons so that their cluster rises only as fast as the lift of all its balloons allows.
+///
+/// Masses and lifts are in units of weight, so gravity cancels out.
+pub fn handle_balloon_lift(
+    mut balloons: Query<
+        (&Balloon, &ReadMassProperties, &mut GravityScale, Entity),
+        Without<IgnoreCollisions>,
+    >,
+    masses: Query<&ReadMassProperties>,
+    stuck_items: Query<&StuckItems>,
+) {
+    let stuck_items = stuck_items.single();
+    let lifts = balloons
+        .iter()
+        .map(|(balloon, _, _, entity)| (entity, *balloon))
+        .collect::<HashMap<_, _>>();
+
+    'balloons: for (balloon, mass, mut gravity, entity) in balloons.iter_mut() {
+        // Balloon that isn't stuck to anything is a cluster of its own
+        let cluster = match stuck_items.cluster(entity) {
+            Some(id) => stuck_items.members(id).collect(),
+            None => vec![entity],
+        };
+
+        let mut total_mass = 0.;
+        let mut total_lift = 0.;
+        let mut balloon_mass = 0.;
+        let mut max_rise = f32::MAX;
+        for e in cluster {
+            // Glued items all have mass, and counting one as weightless would launch the cluster
+            let m = match masses.get(e) {
+                Ok(m) => m.0.mass,
+                Err(_) => {
+                    warn!("Balloon {entity:?} is glued to {e:?}, which has no mass");
+                    continue 'balloons;
+                }
+            };
+            total_mass += m;
+            if let Some(b) = lifts.get(&e) {
+                total_lift += b.lift;
+                balloon_mass += m;
+                max_rise = max_rise.min(b.max_rise);
+            }
+        }
+        if mass.0.mass <= 0. || total_lift <= 0. {
+            continue;
+        }
+
+        gravity.0 = lift_gravity_scale(
+            balloon.lift,
+            mass.0.mass,
+            total_lift,
+            total_mass,
+            balloon_mass,
+            max_rise,
+        );
+    }
+}
+
+/// Pops balloons that touch hazards or sharp items
+pub fn handle_balloon_popping(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut collision_events: EventReader<CollisionEvent>,
+    parents: Query<&Parent>,
+    balloons: Query<&GlobalTransform, With<Balloon>>,
+    poppers: Query<(), Or<(With<Sharp>, With<Destroyer>)>>,
+    impulse_joints: Query<(&ImpulseJoint, &Parent, Entity)>,
+    mut stuck_items: Query<&mut StuckItems>,
+    mut players: Query<&mut Player>,
+) {
+    let mut stuck_items = stuck_items.single_mut();
+    let mut popped = HashSet::new();
+    for collision_event in collision_events.iter() {
+        if let CollisionEvent::Started(a, b, flags) = collision_event {
+            if flags.contains(CollisionEventFlags::SENSOR) {
+                continue;
+            }
+            for (collider, other) in [(*a, *b), (*b, *a)] {
+                let entity = body_of(collider, parents.get(collider).ok());
+                if balloons.contains(entity) && poppers.contains(other) && popped.insert(entity) {
+                    let pos = balloons.get(entity).unwrap().translation();
+                    despawn_item(
+                        &mut commands,
+                        entity,
+                        &impulse_joints,
+                        &mut stuck_items,
+                        &mut players,
+                    );
+                    spawn_pop(&mut commands, &asset_server, pos.truncate());
+                }
+            }
+        }
+    }
+}
+
+fn spawn_pop(commands: &mut Commands, asset_server: &AssetServer, pos: Vec2) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "pop!",
+                TextStyle {
+                    font: asset_server.load("fonts/MajorMonoDisplay-Regular.ttf"),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(pos.x, pos.y, 10.),
+            ..default()
+        },
+        DeathTimer(Timer::from_seconds(0.3, TimerMode::Once)),
+        OnGame,
+    ));
+}
@@ -0,0 +1,160 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::{prelude::*, rapier::prelude::CollisionEventFlags};
+use serde::Deserialize;
+
+use super::{
+    cluster::StuckItems,
+    physics::{body_of, despawn_item},
+    platform::Platform,
+    throw::{IgnoreCollisions, Player},
+    DeathTimer, OnGame,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct BombDefinition {
+    pub trigger: TriggerDefinition,
+    /// Distance the blast reaches
+    pub radius: f32,
+    /// Impulse applied at the center of the blast. Falls off linearly to zero at the radius.
+    pub impulse: f32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum TriggerDefinition {
+    /// Explodes this many seconds after being thrown or dropped
+    Fuse(f32),
+    /// Explodes when it hits anything
+    Impact,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Bomb {
+    pub radius: f32,
+    pub impulse: f32,
+    /// Fuse bombs tick this down once they are in play
+    pub fuse: Option<Timer>,
+}
+
+impl Bomb {
+    pub fn new(definition: &BombDefinition) -> Self {
+        Self {
+            radius: definition.radius,
+            impulse: definition.impulse,
+            fuse: match definition.trigger {
+                TriggerDefinition::Fuse(seconds) => {
+                    Some(Timer::from_seconds(seconds, TimerMode::Once))
+                }
+                TriggerDefinition::Impact => None,
+            },
+        }
+    }
+}
+
+pub fn handle_bombs(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut collision_events: EventReader<CollisionEvent>,
+    parents: Query<&Parent>,
+    // Queued and held items are not in play yet
+    mut bombs: Query<(&mut Bomb, &GlobalTransform, Entity), Without<IgnoreCollisions>>,
+    mut bodies: Query<(&RigidBody, &GlobalTransform, &mut ExternalImpulse, Entity)>,
+    impulse_joints: Query<(&ImpulseJoint, &Parent, Entity)>,
+    transforms: Query<&GlobalTransform>,
+    platforms: Query<&Platform>,
+    mut stuck_items: Query<&mut StuckItems>,
+    mut players: Query<&mut Player>,
+) {
+    // Bombs go off in the order they were set off, so that seeded games play out the same
+    let mut exploding = Vec::new();
+    for (mut bomb, _, entity) in bombs.iter_mut() {
+        if let Some(fuse) = &mut bomb.fuse {
+            if fuse.tick(time.delta()).just_finished() {
+                exploding.push(entity);
+            }
+        }
+    }
+    for collision_event in collision_events.iter() {
+        if let CollisionEvent::Started(a, b, flags) = collision_event {
+            if flags.contains(CollisionEventFlags::SENSOR) {
+                continue;
+            }
+            for collider in [*a, *b] {
+                let entity = body_of(collider, parents.get(collider).ok());
+                if let Ok((bomb, _, _)) = bombs.get(entity) {
+                    if bomb.fuse.is_none() && !exploding.contains(&entity) {
+                        exploding.push(entity);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut stuck_items = stuck_items.single_mut();
+    for &entity in &exploding {
+        let (bomb, transform, _) = bombs.get(entity).unwrap();
+        let center = transform.translation().xy();
+
+        // Push everything around away from the blast. Blasts add up, and bombs going off
+        // together don't push each other as they are gone already.
+        for (body, transform, mut external_impulse, other) in bodies.iter_mut() {
+            if *body != RigidBody::Dynamic || exploding.contains(&other) {
+                continue;
+            }
+            let offset = transform.translation().xy() - center;
+            let distance = offset.length();
+            if distance < bomb.radius {
+                let falloff = 1. - distance / bomb.radius;
+                external_impulse.impulse += offset.normalize_or_zero() * bomb.impulse * falloff;
+            }
+        }
+
+        // Blow apart the glue within the blast
+        for (joint, owner, joint_entity) in impulse_joints.iter() {
+            let owner = owner.get();
+            let anchor = transforms.get(owner).map(|t| {
+                t.transform_point(joint.data.local_anchor2().extend(0.))
+                    .xy()
+            });
+            let blown = anchor.is_ok_and(|anchor| anchor.distance(center) < bomb.radius);
+            if blown {
+                commands.entity(joint_entity).despawn_recursive();
+                if platforms.contains(joint.parent) {
+                    // Item is knocked off the platform, which was never part of its cluster
+                    stuck_items.unanchor(owner, joint.parent);
+                } else {
+                    stuck_items.unglue(owner, joint.parent);
+                }
+            }
+        }
+
+        despawn_item(
+            &mut commands,
+            entity,
+            &impulse_joints,
+            &mut stuck_items,
+            &mut players,
+        );
+        spawn_explosion(&mut commands, &asset_server, center, bomb.radius);
+    }
+}
+
+fn spawn_explosion(commands: &mut Commands, asset_server: &AssetServer, pos: Vec2, radius: f32) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "💥",
+                TextStyle {
+                    font: asset_server.load("fonts/NotoEmoji-VariableFont_wght.ttf"),
+                    font_size: radius,
+                    color: Color::ORANGE_RED,
+                },
+            )
+            .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(pos.x, pos.y, 10.),
+            ..default()
+        },
+        DeathTimer(Timer::from_seconds(0.5, TimerMode::Once)),
+        OnGame,
+    ));
+}
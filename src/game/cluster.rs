@@ -0,0 +1,465 @@
+use bevy::{
+    ecs::entity::Entities,
+    math::Vec3Swizzles,
+    prelude::*,
+    utils::{HashSet, StableHashMap, StableHashSet},
+};
+use bevy_rapier2d::prelude::*;
+
+use super::{physics::item_colliders, GameClock, OnGame, FLOOR};
+
+/// Connected group of glued items the item belongs to.
+///
+/// Written from [`StuckItems`] so that the contact filter hook can read it without locking.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClusterId(pub u32);
+
+/// Graph of the items glued together.
+///
+/// Iterates in the same order on every run, so that seeded games play out the same.
+#[derive(Component, Default)]
+pub struct StuckItems {
+    /// Items each item is glued to
+    glued: StableHashMap<Entity, StableHashSet<Entity>>,
+    /// Platforms each item is glued to, which hold items without joining their clusters
+    anchors: StableHashMap<Entity, StableHashSet<Entity>>,
+    clusters: StableHashMap<Entity, ClusterId>,
+    members: StableHashMap<ClusterId, StableHashSet<Entity>>,
+    next_id: u32,
+    /// Items whose cluster changed since it was last written to [`ClusterId`]
+    changed: StableHashSet<Entity>,
+}
+
+impl StuckItems {
+    pub fn glue(&mut self, e1: Entity, e2: Entity) {
+        if e1 == e2 {
+            return;
+        }
+        self.glued.entry(e1).or_default().insert(e2);
+        self.glued.entry(e2).or_default().insert(e1);
+        match (self.cluster(e1), self.cluster(e2)) {
+            (Some(c1), Some(c2)) if c1 == c2 => {}
+            // Smaller cluster joins the bigger one
+            (Some(c1), Some(c2)) if self.members[&c1].len() >= self.members[&c2].len() => {
+                self.relabel(e2, c1)
+            }
+            (_, Some(c2)) => self.relabel(e1, c2),
+            (Some(c1), None) => self.relabel(e2, c1),
+            (None, None) => {
+                let id = self.new_id();
+                self.relabel(e1, id);
+            }
+        }
+    }
+
+    /// Removes the glue between the items, which may split their cluster in two
+    pub fn unglue(&mut self, e1: Entity, e2: Entity) {
+        let removed = self.glued.get_mut(&e1).is_some_and(|g| g.remove(&e2));
+        if let Some(glued) = self.glued.get_mut(&e2) {
+            glued.remove(&e1);
+        }
+        if removed {
+            self.split(&[e1, e2]);
+        }
+    }
+
+    /// Glues the item to the platform
+    pub fn anchor(&mut self, item: Entity, platform: Entity) {
+        self.anchors.entry(item).or_default().insert(platform);
+    }
+
+    pub fn unanchor(&mut self, item: Entity, platform: Entity) {
+        if let Some(anchors) = self.anchors.get_mut(&item) {
+            anchors.remove(&platform);
+            if anchors.is_empty() {
+                self.anchors.remove(&item);
+            }
+        }
+    }
+
+    pub fn is_anchored_to(&self, item: Entity, platform: Entity) -> bool {
+        self.anchors
+            .get(&item)
+            .is_some_and(|anchors| anchors.contains(&platform))
+    }
+
+    /// Removes the item and everything glued to it
+    pub fn remove_entity(&mut self, e: Entity) {
+        self.anchors.remove(&e);
+        let neighbours = self.glued.remove(&e).unwrap_or_default();
+        for n in &neighbours {
+            if let Some(glued) = self.glued.get_mut(n) {
+                glued.remove(&e);
+            }
+        }
+        if let Some(id) = self.clusters.remove(&e) {
+            self.remove_member(id, e);
+        }
+        // Despawned items don't need their id updated
+        self.changed.remove(&e);
+        self.split(&neighbours.into_iter().collect::<Vec<_>>());
+    }
+
+    pub fn cluster(&self, e: Entity) -> Option<ClusterId> {
+        self.clusters.get(&e).copied()
+    }
+
+    /// Items in the cluster
+    pub fn members(&self, id: ClusterId) -> impl Iterator<Item = Entity> + '_ {
+        self.members.get(&id).into_iter().flatten().copied()
+    }
+
+    pub fn clusters(&self) -> impl Iterator<Item = ClusterId> + '_ {
+        self.members.keys().copied()
+    }
+
+    /// Number of items in the cluster the item belongs to
+    pub fn cluster_size(&self, e: Entity) -> usize {
+        self.cluster(e)
+            .and_then(|id| self.members.get(&id))
+            .map_or(1, |members| members.len())
+    }
+
+    /// Finds connected parts around the items again after glue was removed
+    fn split(&mut self, items: &[Entity]) {
+        let mut visited = HashSet::new();
+        let mut kept = HashSet::new();
+        for &e in items {
+            if visited.contains(&e) {
+                continue;
+            }
+            let component = self.connected(e);
+            visited.extend(component.iter().copied());
+            let old = self.cluster(e);
+            if component.len() <= 1 {
+                // Nothing is glued to the item anymore
+                self.glued.remove(&e);
+                if let Some(old) = self.clusters.remove(&e) {
+                    self.remove_member(old, e);
+                    self.changed.insert(e);
+                }
+                continue;
+            }
+            // First part keeps the old id so that clusters don't change needlessly
+            let id = match old {
+                Some(old) if kept.insert(old) => old,
+                _ => self.new_id(),
+            };
+            self.relabel(e, id);
+        }
+    }
+
+    fn connected(&self, start: Entity) -> StableHashSet<Entity> {
+        let mut component = StableHashSet::from_iter([start]);
+        let mut stack = vec![start];
+        while let Some(e) = stack.pop() {
+            for &n in self.glued.get(&e).into_iter().flatten() {
+                if component.insert(n) {
+                    stack.push(n);
+                }
+            }
+        }
+        component
+    }
+
+    /// Moves everything connected to the item to the cluster
+    fn relabel(&mut self, start: Entity, id: ClusterId) {
+        for e in self.connected(start) {
+            if let Some(old) = self.clusters.insert(e, id) {
+                if old == id {
+                    continue;
+                }
+                self.remove_member(old, e);
+            }
+            self.members.entry(id).or_default().insert(e);
+            self.changed.insert(e);
+        }
+    }
+
+    fn remove_member(&mut self, id: ClusterId, e: Entity) {
+        if let Some(members) = self.members.get_mut(&id) {
+            members.remove(&e);
+            if members.is_empty() {
+                self.members.remove(&id);
+            }
+        }
+    }
+
+    fn new_id(&mut self) -> ClusterId {
+        self.next_id += 1;
+        ClusterId(self.next_id)
+    }
+}
+
+/// Writes changed clusters to [`ClusterId`] components before the physics step
+pub fn handle_cluster_ids(mut commands: Commands, mut stuck_items: Query<&mut StuckItems>) {
+    let stuck_items = &mut *stuck_items.single_mut();
+    for e in stuck_items.changed.drain() {
+        let id = stuck_items.clusters.get(&e).copied();
+        commands.add(move |world: &mut World| {
+            if let Some(mut entity) = world.get_entity_mut(e) {
+                match id {
+                    Some(id) => {
+                        entity.insert(id);
+                    }
+                    None => {
+                        entity.remove::<ClusterId>();
+                    }
+                }
+            } else {
+                // Item was despawned before it got its id, so removal of the id can't be noticed
+                for mut stuck_items in world.query::<&mut StuckItems>().iter_mut(world) {
+                    stuck_items.remove_entity(e);
+                }
+            }
+        });
+    }
+}
+
+/// Forgets items that were despawned while glued
+pub fn handle_cluster_removals(
+    removals: RemovedComponents<ClusterId>,
+    entities: &Entities,
+    mut stuck_items: Query<&mut StuckItems>,
+) {
+    for mut stuck_items in stuck_items.iter_mut() {
+        for entity in removals.iter() {
+            if !entities.contains(entity) {
+                stuck_items.remove_entity(entity);
+            }
+        }
+    }
+}
+
+/// Properties of a connected group of glued items
+#[derive(Component, Clone, Debug)]
+pub struct Cluster {
+    pub id: ClusterId,
+    pub members: usize,
+    pub mass: f32,
+    pub center_of_mass: Vec2,
+    /// Axis-aligned bounds of the colliders
+    pub min: Vec2,
+    pub max: Vec2,
+    /// How far the top of the cluster is above the floor
+    pub height: f32,
+    /// Seconds since the game started
+    pub created: f32,
+}
+
+impl Cluster {
+    fn new(id: ClusterId, created: f32) -> Self {
+        Self {
+            id,
+            members: 0,
+            mass: 0.,
+            center_of_mass: Vec2::ZERO,
+            min: Vec2::splat(f32::MAX),
+            max: Vec2::splat(f32::MIN),
+            height: 0.,
+            created,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        (self.max - self.min).max(Vec2::ZERO)
+    }
+}
+
+/// Keeps a [`Cluster`] entity for each cluster and measures it every frame
+pub fn handle_clusters(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    rapier_context: Res<RapierContext>,
+    stuck_items: Query<&StuckItems>,
+    mut clusters: Query<(&mut Cluster, Entity)>,
+    bodies: Query<(&GlobalTransform, &ReadMassProperties, Option<&Children>)>,
+    colliders: Query<&RapierColliderHandle>,
+) {
+    let stuck_items = stuck_items.single();
+    let mut existing = HashSet::new();
+    for (mut cluster, entity) in clusters.iter_mut() {
+        if stuck_items.members.contains_key(&cluster.id) {
+            existing.insert(cluster.id);
+            measure(
+                &mut cluster,
+                stuck_items,
+                &rapier_context,
+                &bodies,
+                &colliders,
+            );
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    for id in stuck_items.clusters() {
+        if !existing.contains(&id) {
+            let mut cluster = Cluster::new(id, clock.0.elapsed_secs());
+            measure(
+                &mut cluster,
+                stuck_items,
+                &rapier_context,
+                &bodies,
+                &colliders,
+            );
+            commands.spawn((cluster, OnGame));
+        }
+    }
+}
+
+fn measure(
+    cluster: &mut Cluster,
+    stuck_items: &StuckItems,
+    rapier_context: &RapierContext,
+    bodies: &Query<(&GlobalTransform, &ReadMassProperties, Option<&Children>)>,
+    colliders: &Query<&RapierColliderHandle>,
+) {
+    let (mut mass, mut moment) = (0., Vec2::ZERO);
+    let (mut min, mut max) = (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN));
+    let mut members = 0;
+    for e in stuck_items.members(cluster.id) {
+        members += 1;
+        if let Ok((transform, mass_properties, children)) = bodies.get(e) {
+            let properties = &mass_properties.0;
+            let center = transform
+                .transform_point(properties.local_center_of_mass.extend(0.))
+                .xy();
+            mass += properties.mass;
+            moment += properties.mass * center;
+
+            if let Some((item_min, item_max)) = item_bounds(e, children, rapier_context, colliders)
+            {
+                min = min.min(item_min);
+                max = max.max(item_max);
+            }
+        }
+    }
+    cluster.members = members;
+    cluster.mass = mass;
+    if mass > 0. {
+        cluster.center_of_mass = moment / mass;
+    }
+    if min.x <= max.x {
+        cluster.min = min;
+        cluster.max = max;
+        cluster.height = max.y - FLOOR;
+    }
+}
+
+/// Axis-aligned bounds of the colliders of the item
+pub fn item_bounds(
+    e: Entity,
+    children: Option<&Children>,
+    rapier_context: &RapierContext,
+    colliders: &Query<&RapierColliderHandle>,
+) -> Option<(Vec2, Vec2)> {
+    let scale = rapier_context.physics_scale();
+    item_colliders(e, children)
+        .filter_map(|part| colliders.get(part).ok())
+        .filter_map(|handle| rapier_context.colliders.get(handle.0))
+        .map(|collider| {
+            let aabb = collider.compute_aabb();
+            (
+                Vec2::new(aabb.mins.x, aabb.mins.y) * scale,
+                Vec2::new(aabb.maxs.x, aabb.maxs.y) * scale,
+            )
+        })
+        .reduce(|(min1, max1), (min2, max2)| (min1.min(min2), max1.max(max2)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(n: u32) -> Vec<Entity> {
+        (0..n).map(Entity::from_raw).collect()
+    }
+
+    fn chain(stuck_items: &mut StuckItems, items: &[Entity]) {
+        for pair in items.windows(2) {
+            stuck_items.glue(pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn ungluing_a_chain_splits_it() {
+        let e = items(4);
+        let mut stuck_items = StuckItems::default();
+        chain(&mut stuck_items, &e);
+        let id = stuck_items.cluster(e[0]).unwrap();
+        assert!(e.iter().all(|&e| stuck_items.cluster(e) == Some(id)));
+
+        stuck_items.unglue(e[1], e[2]);
+        let (left, right) = (stuck_items.cluster(e[0]), stuck_items.cluster(e[3]));
+        assert!(left.is_some() && right.is_some());
+        assert_ne!(left, right);
+        assert_eq!(stuck_items.cluster(e[1]), left);
+        assert_eq!(stuck_items.cluster(e[2]), right);
+        assert_eq!(stuck_items.cluster_size(e[0]), 2);
+        assert_eq!(stuck_items.cluster_size(e[3]), 2);
+    }
+
+    #[test]
+    fn ungluing_a_cycle_keeps_one_cluster() {
+        let e = items(3);
+        let mut stuck_items = StuckItems::default();
+        chain(&mut stuck_items, &e);
+        stuck_items.glue(e[2], e[0]);
+        let id = stuck_items.cluster(e[0]);
+
+        stuck_items.unglue(e[0], e[1]);
+        assert!(e.iter().all(|&e| stuck_items.cluster(e) == id));
+        assert_eq!(stuck_items.cluster_size(e[1]), 3);
+    }
+
+    #[test]
+    fn removing_a_bridge_splits_its_neighbours() {
+        let e = items(5);
+        let mut stuck_items = StuckItems::default();
+        chain(&mut stuck_items, &e);
+
+        stuck_items.remove_entity(e[2]);
+        assert_eq!(stuck_items.cluster(e[2]), None);
+        assert_ne!(stuck_items.cluster(e[1]), stuck_items.cluster(e[3]));
+        assert_eq!(stuck_items.cluster(e[0]), stuck_items.cluster(e[1]));
+        assert_eq!(stuck_items.cluster(e[3]), stuck_items.cluster(e[4]));
+
+        // Lone items aren't in any cluster
+        stuck_items.remove_entity(e[1]);
+        assert_eq!(stuck_items.cluster(e[0]), None);
+        assert_eq!(stuck_items.cluster_size(e[0]), 1);
+    }
+
+    #[test]
+    fn smaller_cluster_is_relabelled() {
+        let e = items(5);
+        let mut stuck_items = StuckItems::default();
+        chain(&mut stuck_items, &e[..3]);
+        chain(&mut stuck_items, &e[3..]);
+        let big = stuck_items.cluster(e[0]);
+
+        stuck_items.glue(e[3], e[2]);
+        assert!(e.iter().all(|&e| stuck_items.cluster(e) == big));
+        assert_eq!(stuck_items.members(big.unwrap()).count(), 5);
+    }
+
+    #[test]
+    fn changes_are_tracked() {
+        let e = items(4);
+        let mut stuck_items = StuckItems::default();
+        chain(&mut stuck_items, &e[..2]);
+        assert_eq!(stuck_items.changed, StableHashSet::from_iter([e[0], e[1]]));
+
+        stuck_items.changed.clear();
+        stuck_items.glue(e[1], e[2]);
+        assert_eq!(stuck_items.changed, StableHashSet::from_iter([e[2]]));
+
+        // Removed items don't need their id written anymore
+        stuck_items.changed.clear();
+        stuck_items.remove_entity(e[2]);
+        assert!(stuck_items.changed.is_empty());
+
+        stuck_items.unglue(e[0], e[1]);
+        assert_eq!(stuck_items.changed, StableHashSet::from_iter([e[0], e[1]]));
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    cluster::{item_bounds, ClusterId},
+    physics::Glue,
+    throw::{IgnoreCollisions, Player, Throwable},
+    Disabler, OnGame,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+/// Drawn on top of everything else
+const OVERLAY_Z: f32 = 50.;
+const LINE_WIDTH: f32 = 2.;
+const ANCHOR_SIZE: f32 = 8.;
+
+/// Whether the debug overlay is drawn
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+/// Shapes of the overlay, which are redrawn every frame
+#[derive(Component)]
+pub struct DebugShape;
+
+pub fn handle_debug_toggle(keys: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keys.just_pressed(TOGGLE_KEY) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+/// Draws glue joints, clusters and the entities that change how collisions work
+pub fn handle_debug_overlay(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    asset_server: Res<AssetServer>,
+    rapier_context: Res<RapierContext>,
+    shapes: Query<Entity, With<DebugShape>>,
+    impulse_joints: Query<(&ImpulseJoint, &Parent), With<Glue>>,
+    transforms: Query<&GlobalTransform>,
+    throwables: Query<(Entity, Option<&ClusterId>, Option<&Children>), With<Throwable>>,
+    markers: Query<
+        (
+            Entity,
+            Option<&Children>,
+            Option<&IgnoreCollisions>,
+            Option<&Disabler>,
+        ),
+        Or<(With<IgnoreCollisions>, With<Disabler>)>,
+    >,
+    colliders: Query<&RapierColliderHandle>,
+    players: Query<&Player>,
+) {
+    for shape in shapes.iter() {
+        commands.entity(shape).despawn_recursive();
+    }
+    if !overlay.enabled {
+        return;
+    }
+
+    for (joint, parent) in impulse_joints.iter() {
+        if let (Ok(owner), Ok(other)) = (transforms.get(parent.get()), transforms.get(joint.parent))
+        {
+            let anchor1 = other
+                .transform_point(joint.data.local_anchor1().extend(0.))
+                .xy();
+            let anchor2 = owner
+                .transform_point(joint.data.local_anchor2().extend(0.))
+                .xy();
+            spawn_line(&mut commands, anchor1, anchor2, Color::YELLOW);
+            spawn_box(
+                &mut commands,
+                anchor1,
+                Vec2::splat(ANCHOR_SIZE),
+                Color::ORANGE,
+            );
+            spawn_box(&mut commands, anchor2, Vec2::splat(ANCHOR_SIZE), Color::RED);
+        }
+    }
+
+    for (entity, cluster, children) in throwables.iter() {
+        if let Some((min, max)) = item_bounds(entity, children, &rapier_context, &colliders) {
+            spawn_outline(&mut commands, min, max, cluster_color(cluster));
+        }
+    }
+
+    let font = asset_server.load("fonts/MajorMonoDisplay-Regular.ttf");
+    for (entity, children, ignore, disabler) in markers.iter() {
+        let labels = [
+            ignore.map(|_| ("ignore", Color::GRAY)),
+            disabler.map(|_| ("disabler", Color::PURPLE)),
+        ];
+        let labels = labels.into_iter().flatten().collect::<Vec<_>>();
+        if labels.is_empty() {
+            continue;
+        }
+        if let Some((min, max)) = item_bounds(entity, children, &rapier_context, &colliders) {
+            for (i, &(label, color)) in labels.iter().enumerate() {
+                let grow = Vec2::splat(4. * (i + 1) as f32);
+                spawn_outline(&mut commands, min - grow, max + grow, color);
+                let pos = Vec2::new(min.x, max.y + 20. * (i + 1) as f32);
+                spawn_label(&mut commands, &font, pos, label, color);
+            }
+        }
+    }
+
+    for player in players.iter() {
+        let mut disables = player
+            .disables
+            .iter()
+            .map(|e| format!("{e:?}"))
+            .collect::<Vec<_>>();
+        disables.sort();
+        let text = format!("disables: [{}]", disables.join(", "));
+        spawn_label(
+            &mut commands,
+            &font,
+            Vec2::new(-900., -500.),
+            &text,
+            Color::WHITE,
+        );
+    }
+}
+
+/// Different clusters get clearly different hues, and loose items are white
+fn cluster_color(cluster: Option<&ClusterId>) -> Color {
+    match cluster {
+        Some(ClusterId(id)) => Color::hsl((*id as f32 * 137.5) % 360., 0.9, 0.6),
+        None => Color::WHITE,
+    }
+}
+
+fn spawn_line(commands: &mut Commands, from: Vec2, to: Vec2, color: Color) {
+    let delta = to - from;
+    let center = 0.5 * (from + to);
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new(delta.length().max(LINE_WIDTH), LINE_WIDTH)),
+                ..default()
+            },
+            transform: Transform::from_translation(center.extend(OVERLAY_Z))
+                .with_rotation(Quat::from_rotation_z(delta.y.atan2(delta.x))),
+            ..default()
+        },
+        DebugShape,
+        OnGame,
+    ));
+}
+
+fn spawn_box(commands: &mut Commands, center: Vec2, size: Vec2, color: Color) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            transform: Transform::from_translation(center.extend(OVERLAY_Z)),
+            ..default()
+        },
+        DebugShape,
+        OnGame,
+    ));
+}
+
+fn spawn_outline(commands: &mut Commands, min: Vec2, max: Vec2, color: Color) {
+    let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+    for i in 0..corners.len() {
+        spawn_line(
+            commands,
+            corners[i],
+            corners[(i + 1) % corners.len()],
+            color,
+        );
+    }
+}
+
+fn spawn_label(commands: &mut Commands, font: &Handle<Font>, pos: Vec2, text: &str, color: Color) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                text,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    color,
+                },
+            )
+            .with_alignment(TextAlignment::BOTTOM_LEFT),
+            transform: Transform::from_translation(pos.extend(OVERLAY_Z)),
+            ..default()
+        },
+        DebugShape,
+        OnGame,
+    ));
+}
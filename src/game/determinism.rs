@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::utils::arg_value;
+
+/// Seconds each frame takes in the deterministic mode
+pub const TIMESTEP: f32 = 1. / 60.;
+
+/// Deterministic mode is turned on by giving the seed with `--seed <seed>`.
+///
+/// Physics and gameplay are then stepped by [`TIMESTEP`] every frame, and the same seed and inputs
+/// give the same game on the same machine.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct Determinism {
+    pub seed: Option<u64>,
+}
+
+impl Determinism {
+    pub fn from_args() -> Self {
+        Self {
+            seed: arg_value("--seed").and_then(|seed| seed.parse().ok()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// Games get different seeds unless the seed was given
+    pub fn game_seed(&self) -> u64 {
+        self.seed.unwrap_or_else(rand::random)
+    }
+}
+
+/// Seed every random number generator of the current game is seeded from
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct RunSeed(pub u64);
+
+pub fn setup_determinism(
+    determinism: Res<Determinism>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if determinism.enabled() {
+        rapier_config.timestep_mode = TimestepMode::Fixed {
+            dt: TIMESTEP,
+            substeps: 1,
+        };
+    }
+}
+
+/// Replaces the measured frame time with the fixed timestep, so that timers tick the same on every run
+pub fn handle_fixed_time(
+    determinism: Res<Determinism>,
+    mut time: ResMut<Time>,
+    mut fixed: Local<Option<Time>>,
+) {
+    if !determinism.enabled() {
+        return;
+    }
+    let fixed = fixed.get_or_insert_with(|| time.clone());
+    let now = fixed.last_update().unwrap_or_else(|| fixed.startup());
+    fixed.update_with_instant(now + Duration::from_secs_f32(TIMESTEP));
+    *time = fixed.clone();
+}
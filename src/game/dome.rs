@@ -0,0 +1,111 @@
+use std::f32::consts::PI;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_rapier2d::prelude::*;
+
+use super::physics::CollisionLayer;
+
+pub const DOME_RADIUS: f32 = 260.;
+const DOME_SEGMENTS: usize = 32;
+const DOME_WIDTH: f32 = 6.;
+const DOME_ALPHA: f32 = 0.15;
+const FLASH_ALPHA: f32 = 0.8;
+/// Seconds the dome glows after deflecting something
+const FLASH_TIME: f32 = 0.4;
+
+/// One-way barrier around the throwing position.
+///
+/// Items moving away from the centre pass through it and items moving towards the centre bounce
+/// off, which [`Hooks`](super::physics::Hooks) decides for every contact.
+#[derive(Component)]
+pub struct Dome {
+    flash: Timer,
+}
+
+impl Default for Dome {
+    fn default() -> Self {
+        let mut flash = Timer::from_seconds(FLASH_TIME, TimerMode::Once);
+        flash.tick(flash.duration());
+        Self { flash }
+    }
+}
+
+impl Dome {
+    /// Whether a body at the position moving with the velocity goes through the dome
+    pub fn lets_through(centre: Vec2, position: Vec2, linvel: Vec2) -> bool {
+        linvel.dot(position - centre) > 0.
+    }
+}
+
+#[derive(Component)]
+pub struct DomeSegment;
+
+/// Spawns the dome as an arc over the centre
+pub fn spawn_dome<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    centre: Vec2,
+) -> EntityCommands<'w, 's, 'a> {
+    let points = (0..=DOME_SEGMENTS)
+        .map(|i| Vec2::from_angle(PI * i as f32 / DOME_SEGMENTS as f32) * DOME_RADIUS)
+        .collect::<Vec<_>>();
+    let mut dome = commands.spawn((
+        Dome::default(),
+        Collider::polyline(points.clone(), None),
+        Restitution::coefficient(0.8),
+        ActiveEvents::COLLISION_EVENTS,
+        ActiveHooks::FILTER_CONTACT_PAIRS,
+        CollisionLayer::World,
+        CollisionLayer::World.groups(),
+        SpatialBundle::from_transform(Transform::from_xyz(centre.x, centre.y, 0.)),
+    ));
+    dome.with_children(|children| {
+        for segment in points.windows(2) {
+            let delta = segment[1] - segment[0];
+            let middle = 0.5 * (segment[0] + segment[1]);
+            children.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(0.5, 0.8, 1., DOME_ALPHA),
+                        custom_size: Some(Vec2::new(delta.length(), DOME_WIDTH)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(middle.x, middle.y, 1.)
+                        .with_rotation(Quat::from_rotation_z(delta.y.atan2(delta.x))),
+                    ..default()
+                },
+                DomeSegment,
+            ));
+        }
+    });
+    dome
+}
+
+/// Lights the dome up when it deflects something.
+///
+/// Contacts of outgoing items are filtered out, so every contact that starts is a deflection.
+pub fn handle_dome_deflections(
+    time: Res<Time>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut domes: Query<(&mut Dome, &Children)>,
+    mut segments: Query<&mut Sprite, With<DomeSegment>>,
+) {
+    for collision_event in collision_events.iter() {
+        if let CollisionEvent::Started(a, b, _) = collision_event {
+            for e in [a, b] {
+                if let Ok((mut dome, _)) = domes.get_mut(*e) {
+                    dome.flash.reset();
+                }
+            }
+        }
+    }
+    for (mut dome, children) in domes.iter_mut() {
+        let glow = 1. - dome.flash.tick(time.delta()).percent();
+        for &child in children {
+            if let Ok(mut sprite) = segments.get_mut(child) {
+                sprite
+                    .color
+                    .set_a(DOME_ALPHA + (FLASH_ALPHA - DOME_ALPHA) * glow);
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    physics::{body_of, CollisionLayer},
+    throw::{IgnoreCollisions, Throwable},
+    DeathTimer, GameClock, OnGame,
+};
+
+/// Seconds between two streaks of a field
+const STREAK_INTERVAL: f32 = 0.15;
+/// Seconds a streak is visible
+const STREAK_TIME: f32 = 0.8;
+const STREAK_SPEED: f32 = 250.;
+const STREAK_SIZE: Vec2 = Vec2::new(30., 2.);
+
+/// Force field of a [`Level`](super::level::Level)
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldDefinition {
+    pub kind: FieldKind,
+    pub shape: FieldShape,
+    pub position: (f32, f32),
+    #[serde(default)]
+    pub gust: Option<Gust>,
+}
+
+/// What the field does to bodies in it. Strengths are accelerations, so heavy and light items
+/// are moved alike.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum FieldKind {
+    Wind {
+        acceleration: (f32, f32),
+    },
+    /// Spins bodies around the centre, counterclockwise when the strength is positive
+    Vortex {
+        strength: f32,
+    },
+    /// Pulls bodies towards the centre, weakening towards the edge
+    Well {
+        strength: f32,
+    },
+    Updraft {
+        strength: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum FieldShape {
+    Circle(f32),
+    /// Half extents
+    Rect(f32, f32),
+}
+
+/// Strength of the field that varies over time
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Gust {
+    /// Seconds of one cycle
+    pub period: f32,
+    /// How much the strength swings around its average, as a fraction of it
+    pub amount: f32,
+}
+
+impl FieldShape {
+    fn collider(self) -> Collider {
+        match self {
+            FieldShape::Circle(radius) => Collider::ball(radius),
+            FieldShape::Rect(hx, hy) => Collider::cuboid(hx, hy),
+        }
+    }
+
+    /// Distance from the centre to the farthest point of the shape
+    fn reach(self) -> f32 {
+        match self {
+            FieldShape::Circle(radius) => radius,
+            FieldShape::Rect(hx, hy) => Vec2::new(hx, hy).length(),
+        }
+    }
+
+    /// Evenly spread point of the shape for the nth value of a low discrepancy sequence
+    fn point(self, n: usize) -> Vec2 {
+        let u = (0.5 + n as f32 * 0.754_877_7).fract();
+        let v = (0.5 + n as f32 * 0.569_840_3).fract();
+        match self {
+            FieldShape::Circle(radius) => Vec2::from_angle(TAU * u) * radius * v.sqrt(),
+            FieldShape::Rect(hx, hy) => Vec2::new((2. * u - 1.) * hx, (2. * v - 1.) * hy),
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Field {
+    pub kind: FieldKind,
+    pub shape: FieldShape,
+    pub gust: Option<Gust>,
+    streak_timer: Timer,
+    streaks: usize,
+}
+
+impl Field {
+    /// Acceleration of a body at the position, with the centre of the field and seconds into the game
+    pub fn acceleration(&self, centre: Vec2, position: Vec2, time: f32) -> Vec2 {
+        let offset = position - centre;
+        let acceleration = match self.kind {
+            FieldKind::Wind { acceleration } => Vec2::new(acceleration.0, acceleration.1),
+            FieldKind::Vortex { strength } => offset.perp().normalize_or_zero() * strength,
+            FieldKind::Well { strength } => {
+                let falloff = (1. - offset.length() / self.shape.reach()).max(0.);
+                -offset.normalize_or_zero() * strength * falloff
+            }
+            FieldKind::Updraft { strength } => Vec2::Y * strength,
+        };
+        let gust = self.gust.map_or(1., |gust| {
+            1. + gust.amount * (TAU * time / gust.period).sin()
+        });
+        acceleration * gust
+    }
+}
+
+/// Sum of the accelerations of the fields the shapes overlap.
+///
+/// Shapes are the colliders of a body at the position and rotation of the body, like the trajectory
+/// preview sweeps them, so that predicted throws feel the same fields as real ones.
+pub fn field_acceleration(
+    rapier_context: &RapierContext,
+    fields: &Query<(&Field, &GlobalTransform, Entity)>,
+    parts: &[(Vec2, f32, Collider)],
+    position: Vec2,
+    rotation: f32,
+    time: f32,
+) -> Vec2 {
+    let mut inside = HashSet::new();
+    let is_field = |entity: Entity| fields.contains(entity);
+    let filter = QueryFilter::new()
+        .groups(CollisionLayer::Item.groups().into())
+        .predicate(&is_field);
+    for (offset, part_rotation, collider) in parts {
+        rapier_context.intersections_with_shape(
+            position + Vec2::from_angle(rotation).rotate(*offset),
+            rotation + part_rotation,
+            collider,
+            filter,
+            |entity| {
+                inside.insert(entity);
+                true
+            },
+        );
+    }
+    // Summed in the order of the query, so that the result doesn't depend on hashing
+    fields
+        .iter()
+        .filter(|(_, _, entity)| inside.contains(entity))
+        .map(|(field, transform, _)| {
+            field.acceleration(transform.translation().xy(), position, time)
+        })
+        .sum()
+}
+
+/// Spawns the fields of the level as sensors
+pub fn spawn_fields(commands: &mut Commands, fields: &[FieldDefinition]) {
+    for definition in fields {
+        let (x, y) = definition.position;
+        commands.spawn((
+            Field {
+                kind: definition.kind,
+                shape: definition.shape,
+                gust: definition.gust,
+                streak_timer: Timer::from_seconds(STREAK_INTERVAL, TimerMode::Repeating),
+                streaks: 0,
+            },
+            Sensor,
+            definition.shape.collider(),
+            CollisionLayer::Sensor,
+            CollisionLayer::Sensor.groups(),
+            TransformBundle::from(Transform::from_xyz(x, y, 0.)),
+            OnGame,
+        ));
+    }
+}
+
+/// Pushes throwables that overlap fields.
+///
+/// Adds to the external forces after [`reset_external_forces`](super::physics::reset_external_forces).
+pub fn handle_fields(
+    rapier_context: Res<RapierContext>,
+    clock: Res<GameClock>,
+    fields: Query<(&Field, &GlobalTransform, Entity)>,
+    parents: Query<&Parent>,
+    mut bodies: Query<
+        (&GlobalTransform, &ReadMassProperties, &mut ExternalForce),
+        (With<Throwable>, Without<IgnoreCollisions>),
+    >,
+) {
+    let time = clock.0.elapsed_secs();
+    let mut accelerations = HashMap::new();
+    for (field, field_transform, field_entity) in fields.iter() {
+        let centre = field_transform.translation().xy();
+        // Bodies with many colliders in the field are pushed only once
+        let mut inside = HashSet::new();
+        for (c1, c2, intersecting) in rapier_context.intersections_with(field_entity) {
+            if !intersecting {
+                continue;
+            }
+            let collider = if c1 == field_entity { c2 } else { c1 };
+            let body = body_of(collider, parents.get(collider).ok());
+            if bodies.contains(body) {
+                inside.insert(body);
+            }
+        }
+        for body in inside {
+            if let Ok((transform, _, _)) = bodies.get(body) {
+                let position = transform.translation().xy();
+                *accelerations.entry(body).or_insert(Vec2::ZERO) +=
+                    field.acceleration(centre, position, time);
+            }
+        }
+    }
+
+    for (body, acceleration) in accelerations {
+        if let Ok((_, mass, mut external_force)) = bodies.get_mut(body) {
+            external_force.force += acceleration * mass.0.mass;
+        }
+    }
+}
+
+/// Streak of air showing which way a field pushes
+#[derive(Component)]
+pub struct Streak {
+    velocity: Vec2,
+}
+
+pub fn handle_field_streaks(
+    mut commands: Commands,
+    time: Res<Time>,
+    clock: Res<GameClock>,
+    mut fields: Query<(&mut Field, &GlobalTransform)>,
+    mut streaks: Query<(&Streak, &DeathTimer, &mut Transform, &mut Sprite)>,
+) {
+    for (mut field, transform) in fields.iter_mut() {
+        if !field.streak_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let centre = transform.translation().xy();
+        let position = centre + field.shape.point(field.streaks);
+        field.streaks += 1;
+        let acceleration = field.acceleration(centre, position, clock.0.elapsed_secs());
+        if acceleration == Vec2::ZERO {
+            continue;
+        }
+        let velocity = acceleration.normalize() * STREAK_SPEED;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(1., 1., 1., 0.),
+                    custom_size: Some(STREAK_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_xyz(position.x, position.y, -2.)
+                    .with_rotation(Quat::from_rotation_z(velocity.y.atan2(velocity.x))),
+                ..default()
+            },
+            Streak { velocity },
+            DeathTimer(Timer::from_seconds(STREAK_TIME, TimerMode::Once)),
+            OnGame,
+        ));
+    }
+
+    for (streak, timer, mut transform, mut sprite) in streaks.iter_mut() {
+        transform.translation += (streak.velocity * time.delta_seconds()).extend(0.);
+        // Fades in and out
+        let t = timer.0.percent();
+        sprite.color.set_a(0.4 * (1. - (2. * t - 1.).abs()));
+    }
+}
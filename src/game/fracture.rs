@@ -0,0 +1,550 @@
+use bevy::{
+    ecs::system::EntityCommands,
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::MaterialMesh2dBundle,
+    utils::HashMap,
+};
+use bevy_rapier2d::prelude::*;
+use rand::{rngs::SmallRng, Rng};
+use serde::Deserialize;
+
+use super::{
+    cluster::StuckItems,
+    items::{ColliderDefinition, Item, ItemAssets, ItemDefinition, ShapeDefinition},
+    physics::{body_of, CollisionLayer, Glue},
+    platform::Platform,
+    stickiness::{Stickiness, StickinessPolicy},
+    throw::{Player, Throwable},
+    OnGame, StickyMaterial,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct FractureDefinition {
+    /// Contact force needed to break the item
+    pub threshold: f32,
+    pub fragments: FragmentsDefinition,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum FragmentsDefinition {
+    /// Convex polygons in the same units as colliders
+    Authored(Vec<Vec<(f32, f32)>>),
+    /// Every collider is split to this many random Voronoi cells
+    Voronoi(usize),
+}
+
+/// Piece of a broken item
+#[derive(Component, Clone, Debug)]
+pub struct Fragment {
+    pub definition: Handle<ItemDefinition>,
+    pub radius: f32,
+    /// Convex outline in the coordinates of the broken item
+    pub polygon: Vec<Vec2>,
+    /// Index of the collider definition the piece came from
+    pub collider: usize,
+}
+
+#[derive(Resource)]
+pub struct FractureRng(pub SmallRng);
+
+pub fn handle_fractures(
+    mut commands: Commands,
+    mut force_events: EventReader<ContactForceEvent>,
+    items: ItemAssets,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut custom_materials: ResMut<Assets<StickyMaterial>>,
+    parents: Query<&Parent>,
+    breakables: Query<(
+        &Item,
+        &Transform,
+        &Throwable,
+        &Stickiness,
+        Option<&Velocity>,
+        Option<&GravityScale>,
+    )>,
+    impulse_joints: Query<(Entity, &ImpulseJoint, &Parent, Option<&Glue>)>,
+    platforms: Query<&Platform>,
+    mut stuck_items: Query<&mut StuckItems>,
+    mut players: Query<&mut Player>,
+    mut rng: ResMut<FractureRng>,
+) {
+    // Everything that breaks this frame is known before any glue is moved, so that joints
+    // between two broken items go from fragment to fragment
+    let mut broken = Vec::new();
+    for event in force_events.iter() {
+        for collider in [event.collider1, event.collider2] {
+            let entity = body_of(collider, parents.get(collider).ok());
+            let (item, ..) = match breakables.get(entity) {
+                Ok(breakable) => breakable,
+                Err(_) => continue,
+            };
+            let breaks = items
+                .definition(&item.definition)
+                .and_then(|definition| definition.fracture.as_ref())
+                .is_some_and(|f| event.total_force_magnitude >= f.threshold);
+            if breaks && !broken.contains(&entity) {
+                broken.push(entity);
+            }
+        }
+    }
+
+    let mut shattered = HashMap::new();
+    for &entity in &broken {
+        let (item, transform, throwable, stickiness, velocity, gravity) =
+            breakables.get(entity).unwrap();
+        let definition = items.definition(&item.definition).unwrap();
+        let sprite_size = definition.sprite_size(item.radius);
+        let fragments = fragment_polygons(
+            definition,
+            item.radius,
+            sprite_size,
+            items.outline(definition),
+            &mut rng.0,
+        );
+        if fragments.is_empty() {
+            continue;
+        }
+
+        let velocity = velocity.copied().unwrap_or_default();
+        let fragments = fragments
+            .into_iter()
+            .map(|(polygon, collider)| {
+                let offset = transform
+                    .rotation
+                    .mul_vec3(centroid(&polygon).extend(0.))
+                    .truncate();
+                let mut cmds = spawn_fragment(
+                    &mut commands,
+                    &asset_server,
+                    &mut meshes,
+                    &mut custom_materials,
+                    &items,
+                    &item.definition,
+                    item.radius,
+                    polygon.clone(),
+                    collider,
+                );
+                cmds.insert((
+                    // Fragments share the frame of the broken item so joint anchors stay valid
+                    TransformBundle::from(*transform),
+                    // Pieces keep spinning around the center of the item
+                    Velocity {
+                        linvel: velocity.linvel + offset.perp() * velocity.angvel,
+                        angvel: velocity.angvel,
+                    },
+                    throwable.clone(),
+                    stickiness.clone(),
+                    OnGame,
+                ));
+                if let Some(&gravity) = gravity {
+                    cmds.insert(gravity);
+                }
+                (cmds.id(), polygon)
+            })
+            .collect::<Vec<_>>();
+        shattered.insert(entity, fragments);
+    }
+    if shattered.is_empty() {
+        return;
+    }
+
+    // Broken items are gone through in the order they broke, so that seeded games play out the same
+    broken.retain(|entity| shattered.contains_key(entity));
+    let mut stuck_items = stuck_items.single_mut();
+    for &entity in &broken {
+        stuck_items.remove_entity(entity);
+    }
+    // Move glue joints to the fragments that cover their anchors
+    for (joint_entity, joint, owner, glue) in impulse_joints.iter() {
+        let (parent, owner) = (joint.parent, owner.get());
+        let parent_fragments = shattered.get(&parent);
+        let owner_fragments = shattered.get(&owner);
+        if parent_fragments.is_none() && owner_fragments.is_none() {
+            continue;
+        }
+        let new_parent = parent_fragments.map_or(parent, |fragments| {
+            fragment_at(fragments, joint.data.local_anchor1())
+        });
+        let moved = ImpulseJoint::new(new_parent, joint.data);
+        let new_owner = match owner_fragments {
+            // Joint goes with the broken owner, so the fragment gets a new one
+            Some(fragments) => {
+                let fragment = fragment_at(fragments, joint.data.local_anchor2());
+                let glue = glue.cloned().unwrap_or_default();
+                commands.entity(fragment).with_children(|builder| {
+                    builder.spawn((moved, glue));
+                });
+                fragment
+            }
+            None => match commands.get_entity(joint_entity) {
+                Some(mut joint_commands) => {
+                    joint_commands.insert(moved);
+                    owner
+                }
+                None => continue,
+            },
+        };
+        if platforms.contains(new_parent) {
+            stuck_items.anchor(new_owner, new_parent);
+        } else {
+            stuck_items.glue(new_owner, new_parent);
+        }
+    }
+
+    for &entity in &broken {
+        for mut player in players.iter_mut() {
+            player.disables.remove(&entity);
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Fragment whose polygon covers the anchor, or the closest one
+fn fragment_at(fragments: &[(Entity, Vec<Vec2>)], anchor: Vec2) -> Entity {
+    fragments
+        .iter()
+        .find(|(_, polygon)| contains(polygon, anchor))
+        .or_else(|| {
+            fragments.iter().min_by(|(_, a), (_, b)| {
+                let da = centroid(a).distance_squared(anchor);
+                let db = centroid(b).distance_squared(anchor);
+                da.total_cmp(&db)
+            })
+        })
+        .map(|&(e, _)| e)
+        .unwrap()
+}
+
+/// Spawns a piece of the item with the polygon as its outline.
+///
+/// The polygon is in the coordinates of the whole item, so the piece is placed with the
+/// transform of the item.
+pub fn spawn_fragment<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    asset_server: &AssetServer,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    custom_materials: &mut ResMut<Assets<StickyMaterial>>,
+    items: &ItemAssets,
+    handle: &Handle<ItemDefinition>,
+    radius: f32,
+    polygon: Vec<Vec2>,
+    collider: usize,
+) -> EntityCommands<'w, 's, 'a> {
+    let definition = items
+        .definition(handle)
+        .expect("Item definition should be loaded before spawning");
+    let collider_definition = &definition.colliders[collider];
+    let sprite_size = definition.sprite_size(radius);
+    let mut cmds = commands.spawn((
+        RigidBody::Dynamic,
+        ActiveEvents::COLLISION_EVENTS,
+        ActiveHooks::FILTER_CONTACT_PAIRS,
+        CollisionLayer::Item,
+        CollisionLayer::Item.groups(),
+        Collider::convex_hull(&polygon).unwrap_or_else(|| Collider::ball(0.5 * radius)),
+        Restitution::coefficient(collider_definition.restitution),
+        ColliderMassProperties::Density(collider_definition.density),
+        Ccd::enabled(),
+        Velocity::zero(),
+        ReadMassProperties::default(),
+        ExternalImpulse::default(),
+        ExternalForce::default(),
+        Damping {
+            linear_damping: definition.damping.linear,
+            angular_damping: definition.damping.angular,
+        },
+    ));
+    cmds.insert((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(fragment_mesh(&polygon, sprite_size)).into(),
+            material: custom_materials.add(StickyMaterial {
+                color: Color::LIME_GREEN,
+                color_texture: asset_server.load(definition.sprite.as_str()),
+                sticky: (definition.stickiness == StickinessPolicy::Always) as i32,
+                policy: definition.stickiness.shader_index(),
+                remaining: 1.,
+            }),
+            ..default()
+        },
+        Fragment {
+            definition: handle.clone(),
+            radius,
+            polygon,
+            collider,
+        },
+        Stickiness::new(definition.stickiness),
+    ));
+    cmds
+}
+
+/// Convex polygons the item breaks into paired with the index of the collider they came from
+fn fragment_polygons(
+    definition: &ItemDefinition,
+    radius: f32,
+    sprite_size: Vec2,
+    outline: Option<&[Vec2]>,
+    rng: &mut SmallRng,
+) -> Vec<(Vec<Vec2>, usize)> {
+    let fracture = match &definition.fracture {
+        Some(fracture) if !definition.colliders.is_empty() => fracture,
+        _ => return vec![],
+    };
+    match &fracture.fragments {
+        FragmentsDefinition::Authored(polygons) => polygons
+            .iter()
+            .map(|polygon| {
+                let points = polygon
+                    .iter()
+                    .map(|&(x, y)| Vec2::new(x, y) * radius)
+                    .collect::<Vec<_>>();
+                (convex_hull(&points), 0)
+            })
+            .filter(|(polygon, _)| polygon.len() >= 3)
+            .collect(),
+        &FragmentsDefinition::Voronoi(cells) => definition
+            .colliders
+            .iter()
+            .enumerate()
+            .flat_map(|(i, collider)| {
+                let hull = collider_hull(collider, radius, sprite_size, outline);
+                voronoi(&hull, cells, rng)
+                    .into_iter()
+                    .map(move |cell| (cell, i))
+            })
+            .collect(),
+    }
+}
+
+/// Convex hull of the collider in item coordinates
+fn collider_hull(
+    collider: &ColliderDefinition,
+    radius: f32,
+    sprite_size: Vec2,
+    outline: Option<&[Vec2]>,
+) -> Vec<Vec2> {
+    let offset = Vec2::new(collider.offset.0, collider.offset.1) * radius;
+    let points = match &collider.shape {
+        &ShapeDefinition::Ball(r) => (0..16)
+            .map(|i| Vec2::from_angle(i as f32 / 16. * std::f32::consts::TAU) * r * radius)
+            .collect(),
+        &ShapeDefinition::Cuboid(hx, hy) => {
+            let h = Vec2::new(hx, hy) * radius;
+            vec![-h, Vec2::new(h.x, -h.y), h, Vec2::new(-h.x, h.y)]
+        }
+        ShapeDefinition::Polygon(vertices) => vertices
+            .iter()
+            .map(|&(x, y)| Vec2::new(x, y) * radius)
+            .collect(),
+        ShapeDefinition::Outline => match outline {
+            Some(outline) => outline.iter().map(|&p| p * sprite_size).collect(),
+            None => {
+                let h = 0.5 * sprite_size;
+                vec![-h, Vec2::new(h.x, -h.y), h, Vec2::new(-h.x, h.y)]
+            }
+        },
+    };
+    convex_hull(&points.into_iter().map(|p| p + offset).collect::<Vec<_>>())
+}
+
+/// Splits convex polygon into Voronoi cells around random points
+fn voronoi(polygon: &[Vec2], cells: usize, rng: &mut SmallRng) -> Vec<Vec<Vec2>> {
+    if polygon.len() < 3 || cells <= 1 {
+        return vec![polygon.to_vec()];
+    }
+    let min = polygon.iter().copied().reduce(Vec2::min).unwrap();
+    let max = polygon.iter().copied().reduce(Vec2::max).unwrap();
+    let seeds = (0..cells)
+        .map(|_| Vec2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y)))
+        .collect::<Vec<_>>();
+    seeds
+        .iter()
+        .map(|&seed| {
+            seeds
+                .iter()
+                .filter(|&&other| other != seed)
+                .fold(polygon.to_vec(), |cell, &other| {
+                    // Keep the side of the bisector closer to the seed
+                    clip(&cell, 0.5 * (seed + other), other - seed)
+                })
+        })
+        .filter(|cell| cell.len() >= 3)
+        .collect()
+}
+
+/// Keeps part of the convex polygon where `(p - point) · normal <= 0`
+fn clip(polygon: &[Vec2], point: Vec2, normal: Vec2) -> Vec<Vec2> {
+    let side = |p: Vec2| (p - point).dot(normal);
+    let mut result = vec![];
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (sa, sb) = (side(a), side(b));
+        if sa <= 0. {
+            result.push(a);
+        }
+        if (sa < 0. && sb > 0.) || (sa > 0. && sb < 0.) {
+            result.push(a + (b - a) * (sa / (sa - sb)));
+        }
+    }
+    result
+}
+
+/// Counter-clockwise convex hull with monotone chain
+fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let turns_left = |hull: &[Vec2], p: Vec2| {
+        let (o, a) = (hull[hull.len() - 2], hull[hull.len() - 1]);
+        (a - o).perp_dot(p - o) > 0.
+    };
+    let mut lower: Vec<Vec2> = vec![];
+    for &p in &points {
+        while lower.len() >= 2 && !turns_left(&lower, p) {
+            lower.pop();
+        }
+        lower.push(p);
+    }
+    let mut upper: Vec<Vec2> = vec![];
+    for &p in points.iter().rev() {
+        while upper.len() >= 2 && !turns_left(&upper, p) {
+            upper.pop();
+        }
+        upper.push(p);
+    }
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+fn centroid(polygon: &[Vec2]) -> Vec2 {
+    polygon.iter().copied().sum::<Vec2>() / polygon.len() as f32
+}
+
+fn contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let n = polygon.len();
+    (0..n).all(|i| (polygon[(i + 1) % n] - polygon[i]).perp_dot(point - polygon[i]) >= 0.)
+}
+
+/// Triangle fan over the convex polygon that shows the matching part of the sprite
+fn fragment_mesh(polygon: &[Vec2], sprite_size: Vec2) -> Mesh {
+    let positions = polygon.iter().map(|p| [p.x, p.y, 0.]).collect::<Vec<_>>();
+    let normals = vec![[0., 0., 1.]; polygon.len()];
+    let uvs = polygon
+        .iter()
+        .map(|p| [p.x / sprite_size.x + 0.5, 0.5 - p.y / sprite_size.y])
+        .collect::<Vec<_>>();
+    let indices = (1..polygon.len() as u32 - 1)
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn area(polygon: &[Vec2]) -> f32 {
+        let n = polygon.len();
+        0.5 * (0..n)
+            .map(|i| polygon[i].perp_dot(polygon[(i + 1) % n]))
+            .sum::<f32>()
+    }
+
+    fn square(h: f32) -> Vec<Vec2> {
+        vec![
+            Vec2::new(-h, -h),
+            Vec2::new(h, -h),
+            Vec2::new(h, h),
+            Vec2::new(-h, h),
+        ]
+    }
+
+    #[test]
+    fn fragments_cover_the_hull() {
+        let hull = convex_hull(&[
+            Vec2::new(-40., -10.),
+            Vec2::new(30., -30.),
+            Vec2::new(50., 20.),
+            Vec2::new(0., 40.),
+            Vec2::new(-30., 25.),
+        ]);
+        for seed in 0..10 {
+            let cells = voronoi(&hull, 6, &mut SmallRng::seed_from_u64(seed));
+            assert!(!cells.is_empty());
+            let total = cells.iter().map(|cell| area(cell)).sum::<f32>();
+            assert!((total - area(&hull)).abs() < 0.01 * area(&hull), "{total}");
+            assert!(cells.iter().all(|cell| area(cell) > 0.));
+        }
+    }
+
+    #[test]
+    fn clip_keeps_the_side_against_the_normal() {
+        let clipped = clip(&square(1.), Vec2::ZERO, Vec2::X);
+        assert_eq!(area(&clipped), 2.);
+        assert!(clipped.iter().all(|p| p.x <= 0.));
+
+        assert_eq!(clip(&square(1.), Vec2::new(2., 0.), Vec2::X), square(1.));
+        assert!(clip(&square(1.), Vec2::new(-2., 0.), Vec2::X).is_empty());
+    }
+
+    #[test]
+    fn contains_the_boundary() {
+        let square = square(1.);
+        assert!(contains(&square, Vec2::ZERO));
+        assert!(contains(&square, Vec2::new(1., 0.)));
+        assert!(contains(&square, Vec2::new(1., 1.)));
+        assert!(!contains(&square, Vec2::new(1.01, 0.)));
+    }
+
+    #[test]
+    fn convex_hull_drops_collinear_and_inner_points() {
+        let hull = convex_hull(&[
+            Vec2::new(0., 0.),
+            Vec2::new(1., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 2.),
+            Vec2::new(1., 1.),
+            Vec2::new(0., 2.),
+            Vec2::new(0., 1.),
+        ]);
+        assert_eq!(
+            hull,
+            vec![
+                Vec2::new(0., 0.),
+                Vec2::new(2., 0.),
+                Vec2::new(2., 2.),
+                Vec2::new(0., 2.),
+            ]
+        );
+        // Counter-clockwise
+        assert!(area(&hull) > 0.);
+    }
+
+    #[test]
+    fn fragment_at_falls_back_to_the_closest_centroid() {
+        let (left, right) = (Entity::from_raw(1), Entity::from_raw(2));
+        let fragments = [
+            (left, clip(&square(1.), Vec2::ZERO, Vec2::X)),
+            (right, clip(&square(1.), Vec2::ZERO, -Vec2::X)),
+        ];
+        assert_eq!(fragment_at(&fragments, Vec2::new(-0.5, 0.)), left);
+        assert_eq!(fragment_at(&fragments, Vec2::new(0.5, 0.)), right);
+        // Anchors off the item go to the closest piece
+        assert_eq!(fragment_at(&fragments, Vec2::new(-3., 5.)), left);
+        assert_eq!(fragment_at(&fragments, Vec2::new(4., -1.)), right);
+    }
+}
@@ -0,0 +1,444 @@
+//! Headless app running the gameplay schedule of the game, for testing sticking and scoring
+
+use std::time::Duration;
+
+use bevy::{ecs::system::CommandQueue, math::Vec3Swizzles, prelude::*, utils::HashSet};
+use bevy_rapier2d::prelude::*;
+use rand::{rngs::SmallRng, SeedableRng};
+
+use super::{
+    add_gameplay_systems,
+    bomb::Bomb,
+    cluster::StuckItems,
+    determinism::{Determinism, TIMESTEP},
+    dome::{spawn_dome, DOME_RADIUS},
+    fields::{spawn_fields, FieldDefinition, FieldKind, FieldShape},
+    fracture::FractureRng,
+    items::{ItemCatalog, ItemDefinition, Items},
+    outline::{outline_collider, trace_sprite, Outlines},
+    physics::{CollisionLayer, Hooks, PhysicsScale},
+    platform::{Platform, PlatformPath},
+    shaders::StickyMaterial,
+    spawning::{SpawnTable, SpawnTables},
+    stickiness::{Stickiness, StickinessPolicy},
+    structure::StructureScore,
+    throw::{Player, Throwable},
+    Destroyer, GameClock,
+};
+
+/// Half of the side of the boxes the harness spawns
+pub const HALF: f32 = 25.;
+pub const LIVES: usize = 9;
+/// Sprite mask of the L shaped item whose collider is traced like a sprite outline
+const L_SPRITE: [&str; 4] = ["#...", "#...", "#...", "####"];
+
+/// Runs the gameplay systems in the deterministic mode without a window or renderer
+pub struct Harness {
+    pub app: App,
+    pub player: Entity,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_scale(PhysicsScale::default())
+    }
+
+    pub fn with_scale(scale: PhysicsScale) -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<Scene>()
+            .add_plugin(scale.plugin())
+            .insert_resource(RapierConfiguration {
+                gravity: Vec2::ZERO,
+                timestep_mode: TimestepMode::Fixed {
+                    dt: TIMESTEP,
+                    substeps: 1,
+                },
+                ..default()
+            })
+            .insert_resource(PhysicsHooksWithQueryResource(Box::new(Hooks)))
+            .insert_resource(Determinism { seed: Some(0) })
+            .init_resource::<GameClock>()
+            // Fractures spawn fragments through the item assets, which the harness leaves empty
+            .add_asset::<ItemDefinition>()
+            .add_asset::<ItemCatalog>()
+            .add_asset::<SpawnTable>()
+            .add_asset::<StickyMaterial>()
+            .init_resource::<Outlines>()
+            .insert_resource(Items {
+                catalog: Handle::default(),
+            })
+            .insert_resource(SpawnTables {
+                queue: Handle::default(),
+                drops: Handle::default(),
+            })
+            .insert_resource(FractureRng(SmallRng::seed_from_u64(0)));
+        add_gameplay_systems(&mut app, SystemSet::new());
+
+        app.world.spawn(StuckItems::default());
+        let player = app
+            .world
+            .spawn(Player {
+                lives: LIVES,
+                score: 0,
+                hold_timer: Timer::new(Duration::from_secs_f32(1.), TimerMode::Once),
+                cooldown_timer: Timer::new(Duration::from_millis(200), TimerMode::Once),
+                power_interval: Timer::new(Duration::from_millis(10), TimerMode::Repeating),
+                disables: HashSet::new(),
+                prev_mouse: None,
+            })
+            .id();
+        Self { app, player }
+    }
+
+    /// Spawns a box that dropped from the sky and floats where it is
+    pub fn drop_item(&mut self, pos: Vec2, sticky: bool) -> Entity {
+        let collider = Collider::cuboid(HALF, HALF);
+        self.spawn_item(pos, Vec2::ZERO, collider, Throwable::new(None, sticky))
+    }
+
+    /// Spawns a box the player just threw
+    pub fn throw_item(&mut self, pos: Vec2, velocity: Vec2) -> Entity {
+        let player = self.player;
+        let collider = Collider::cuboid(HALF, HALF);
+        self.spawn_item(pos, velocity, collider, Throwable::new(Some(player), false))
+    }
+
+    /// Spawns an L shaped item the player just threw, with a collider traced from its sprite
+    pub fn throw_traced_item(&mut self, pos: Vec2, velocity: Vec2) -> Entity {
+        let player = self.player;
+        let outline = trace_sprite(4, 4, |x, y| L_SPRITE[y].as_bytes()[x] == b'#').unwrap();
+        let collider = outline_collider(&outline, Vec2::splat(2. * HALF));
+        self.spawn_item(pos, velocity, collider, Throwable::new(Some(player), false))
+    }
+
+    fn spawn_item(
+        &mut self,
+        pos: Vec2,
+        velocity: Vec2,
+        collider: Collider,
+        throwable: Throwable,
+    ) -> Entity {
+        self.app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                collider,
+                ActiveEvents::COLLISION_EVENTS,
+                ActiveHooks::FILTER_CONTACT_PAIRS,
+                CollisionLayer::Item,
+                CollisionLayer::Item.groups(),
+                Velocity::linear(velocity),
+                ReadMassProperties::default(),
+                ExternalImpulse::default(),
+                ExternalForce::default(),
+                TransformBundle::from(Transform::from_xyz(pos.x, pos.y, 0.)),
+                throwable,
+                Stickiness::new(StickinessPolicy::Contagious),
+            ))
+            .id()
+    }
+
+    pub fn spawn_destroyer(&mut self, pos: Vec2) -> Entity {
+        self.app
+            .world
+            .spawn((
+                Collider::cuboid(1000., HALF),
+                CollisionLayer::World,
+                CollisionLayer::World.groups(),
+                Destroyer,
+                TransformBundle::from(Transform::from_xyz(pos.x, pos.y, 0.)),
+            ))
+            .id()
+    }
+
+    pub fn spawn_dome(&mut self, centre: Vec2) -> Entity {
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &self.app.world);
+        let dome = spawn_dome(&mut commands, centre).id();
+        queue.apply(&mut self.app.world);
+        dome
+    }
+
+    pub fn spawn_field(&mut self, definition: FieldDefinition) {
+        let mut queue = CommandQueue::default();
+        spawn_fields(
+            &mut Commands::new(&mut queue, &self.app.world),
+            &[definition],
+        );
+        queue.apply(&mut self.app.world);
+    }
+
+    /// Spawns a dropped box with a bomb whose fuse is already burning
+    pub fn spawn_bomb(&mut self, pos: Vec2, radius: f32, fuse: f32) -> Entity {
+        let bomb = self.drop_item(pos, false);
+        self.app.world.entity_mut(bomb).insert(Bomb {
+            radius,
+            impulse: 50.,
+            fuse: Some(Timer::from_seconds(fuse, TimerMode::Once)),
+        });
+        bomb
+    }
+
+    /// Spawns a platform of the size of a box
+    pub fn spawn_platform(&mut self, pos: Vec2, path: PlatformPath) -> Entity {
+        self.app
+            .world
+            .spawn((
+                Platform {
+                    start: pos,
+                    path,
+                    spin: 0.,
+                },
+                RigidBody::KinematicPositionBased,
+                Collider::cuboid(HALF, HALF),
+                CollisionLayer::World,
+                CollisionLayer::World.groups(),
+                TransformBundle::from(Transform::from_xyz(pos.x, pos.y, 0.)),
+            ))
+            .id()
+    }
+
+    pub fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    /// Glue joints as (parent, owner) pairs
+    pub fn joints(&mut self) -> Vec<(Entity, Entity)> {
+        let mut joints = self.app.world.query::<(&ImpulseJoint, &Parent)>();
+        joints
+            .iter(&self.app.world)
+            .map(|(joint, owner)| (joint.parent, owner.get()))
+            .collect()
+    }
+
+    /// Local anchors of the joint on its (parent, owner)
+    pub fn anchors(&mut self) -> Vec<(Vec2, Vec2)> {
+        let mut joints = self.app.world.query::<&ImpulseJoint>();
+        joints
+            .iter(&self.app.world)
+            .map(|joint| (joint.data.local_anchor1(), joint.data.local_anchor2()))
+            .collect()
+    }
+
+    pub fn stuck_items(&mut self) -> &StuckItems {
+        let mut stuck_items = self.app.world.query::<&StuckItems>();
+        stuck_items.single(&self.app.world)
+    }
+
+    /// Structure bonuses awarded for all clusters so far
+    pub fn structure_bonuses(&mut self) -> usize {
+        let mut scores = self.app.world.query::<&StructureScore>();
+        scores
+            .iter(&self.app.world)
+            .map(|s| s.height + s.overhang + s.balance + s.variety)
+            .sum()
+    }
+
+    pub fn player(&self) -> &Player {
+        self.app.world.get::<Player>(self.player).unwrap()
+    }
+
+    pub fn position(&self, entity: Entity) -> Vec2 {
+        self.app
+            .world
+            .get::<Transform>(entity)
+            .unwrap()
+            .translation
+            .xy()
+    }
+
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.app.world.get_entity(entity).is_some()
+    }
+}
+
+#[test]
+fn sticky_items_glue_and_score() {
+    let mut harness = Harness::new();
+    let dropped = harness.drop_item(Vec2::ZERO, true);
+    let thrown = harness.throw_item(Vec2::new(-200., 0.), Vec2::new(600., 0.));
+    harness.step(60);
+
+    let joints = harness.joints();
+    assert_eq!(joints.len(), 1);
+    let (parent, owner) = joints[0];
+    assert_eq!(
+        HashSet::from_iter([parent, owner]),
+        HashSet::from_iter([dropped, thrown])
+    );
+
+    let stuck_items = harness.stuck_items();
+    assert!(stuck_items.cluster(dropped).is_some());
+    assert_eq!(stuck_items.cluster(dropped), stuck_items.cluster(thrown));
+    assert_eq!(stuck_items.cluster_size(thrown), 2);
+
+    // Only the thrown item has a player to score for
+    let bonuses = harness.structure_bonuses();
+    assert!(bonuses > 0);
+    assert_eq!(harness.player().score, 20 + bonuses);
+    assert_eq!(harness.player().lives, LIVES);
+}
+
+#[test]
+fn items_that_are_not_sticky_bounce_off() {
+    let mut harness = Harness::new();
+    let dropped = harness.drop_item(Vec2::ZERO, false);
+    let thrown = harness.throw_item(Vec2::new(-200., 0.), Vec2::new(600., 0.));
+    harness.step(60);
+
+    assert!(harness.joints().is_empty());
+    assert!(harness.stuck_items().cluster(dropped).is_none());
+    assert!(harness.stuck_items().cluster(thrown).is_none());
+    assert_eq!(harness.player().score, 0);
+}
+
+#[test]
+fn items_stuck_from_both_sides_form_one_cluster() {
+    let mut harness = Harness::new();
+    let dropped = harness.drop_item(Vec2::ZERO, true);
+    let left = harness.throw_item(Vec2::new(-200., 0.), Vec2::new(600., 0.));
+    let right = harness.throw_item(Vec2::new(200., 0.), Vec2::new(-600., 0.));
+    harness.step(60);
+
+    assert_eq!(harness.joints().len(), 2);
+    let stuck_items = harness.stuck_items();
+    let cluster = stuck_items.cluster(dropped);
+    assert!(cluster.is_some());
+    assert_eq!(stuck_items.cluster(left), cluster);
+    assert_eq!(stuck_items.cluster(right), cluster);
+    assert_eq!(stuck_items.members(cluster.unwrap()).count(), 3);
+
+    // Second item to stick gets more for the bigger cluster
+    let bonuses = harness.structure_bonuses();
+    assert_eq!(harness.player().score, 10 * 2 + 10 * 3 + bonuses);
+}
+
+#[test]
+fn thrown_item_lost_to_destroyer_costs_a_life() {
+    let mut harness = Harness::new();
+    harness.spawn_destroyer(Vec2::new(0., -300.));
+    let thrown = harness.throw_item(Vec2::ZERO, Vec2::new(0., -800.));
+    harness.step(60);
+
+    assert!(!harness.exists(thrown));
+    assert_eq!(harness.player().lives, LIVES - 1);
+    assert_eq!(harness.player().score, 0);
+}
+
+#[test]
+fn same_inputs_give_the_same_outcome() {
+    let run = || {
+        let mut harness = Harness::new();
+        harness.drop_item(Vec2::ZERO, true);
+        harness.throw_item(Vec2::new(-200., 30.), Vec2::new(600., -40.));
+        harness.throw_item(Vec2::new(200., -10.), Vec2::new(-550., 20.));
+        let traced = harness.throw_traced_item(Vec2::new(10., 250.), Vec2::new(0., -500.));
+        harness.step(90);
+        let transform = *harness.app.world.get::<Transform>(traced).unwrap();
+        (harness.anchors(), harness.player().score, transform)
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn dome_lets_items_out_but_not_in() {
+    let mut harness = Harness::new();
+    harness.spawn_dome(Vec2::ZERO);
+    let outgoing = harness.throw_item(Vec2::ZERO, Vec2::new(400., 400.));
+    let incoming = harness.throw_item(Vec2::new(0., 2. * DOME_RADIUS), Vec2::new(0., -600.));
+    harness.step(60);
+
+    // Both end up outside, as the outgoing one went through and the incoming one bounced off
+    assert!(harness.position(outgoing).length() > DOME_RADIUS);
+    assert!(harness.position(incoming).length() > DOME_RADIUS);
+}
+
+#[test]
+fn wind_pushes_items_in_it_and_only_them() {
+    let mut harness = Harness::new();
+    harness.spawn_field(FieldDefinition {
+        kind: FieldKind::Wind {
+            acceleration: (1000., 0.),
+        },
+        shape: FieldShape::Rect(200., 200.),
+        position: (0., 0.),
+        gust: None,
+    });
+    let inside = harness.drop_item(Vec2::ZERO, false);
+    let outside = harness.drop_item(Vec2::new(0., 400.), false);
+    harness.step(30);
+
+    // Moves as far as the acceleration takes it, give or take the frame before the field sees it
+    let time = 30. * TIMESTEP;
+    let expected = 0.5 * 1000. * time * time;
+    let moved = harness.position(inside).x;
+    assert!(
+        (moved - expected).abs() < 0.1 * expected,
+        "Moved {moved} instead of {expected}"
+    );
+    assert_eq!(harness.position(outside), Vec2::new(0., 400.));
+}
+
+#[test]
+fn items_stick_to_platforms_and_ride_them() {
+    let mut harness = Harness::new();
+    let platform = harness.spawn_platform(
+        Vec2::ZERO,
+        PlatformPath::Linear {
+            offset: (300., 0.),
+            duration: 1.,
+        },
+    );
+    let player = harness.player;
+    let thrown = harness.spawn_item(
+        Vec2::new(0., 150.),
+        Vec2::new(0., -600.),
+        Collider::cuboid(HALF, HALF),
+        Throwable::new(Some(player), true),
+    );
+    harness.step(30);
+
+    assert_eq!(harness.joints(), vec![(platform, thrown)]);
+    let stuck_items = harness.stuck_items();
+    assert!(stuck_items.is_anchored_to(thrown, platform));
+    // Platform doesn't join the cluster, so the item scores as if it was alone
+    assert!(stuck_items.cluster(thrown).is_none());
+    assert_eq!(harness.player().score, 10);
+
+    // Item keeps where it landed on the platform while the platform moves on
+    let landed = harness.position(thrown) - harness.position(platform);
+    harness.step(60);
+    let ridden = harness.position(thrown) - harness.position(platform);
+    assert!(harness.position(platform).x > 250.);
+    assert!((ridden - landed).length() < 2., "{landed} != {ridden}");
+}
+
+#[test]
+fn blast_knocks_items_off_platforms() {
+    let mut harness = Harness::new();
+    let platform = harness.spawn_platform(Vec2::ZERO, PlatformPath::Still);
+    let player = harness.player;
+    let thrown = harness.spawn_item(
+        Vec2::new(0., 150.),
+        Vec2::new(0., -600.),
+        Collider::cuboid(HALF, HALF),
+        Throwable::new(Some(player), true),
+    );
+    harness.step(30);
+    assert!(harness.stuck_items().is_anchored_to(thrown, platform));
+
+    // Bomb goes off under the platform, close enough to reach the glue on top of it
+    let bomb = harness.spawn_bomb(Vec2::new(0., -100.), 200., 0.1);
+    harness.step(30);
+
+    assert!(!harness.exists(bomb));
+    assert!(harness.joints().is_empty());
+    assert!(!harness.stuck_items().is_anchored_to(thrown, platform));
+}
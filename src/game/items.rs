@@ -1,196 +1,498 @@
-use bevy::{ecs::system::EntityCommands, prelude::*, sprite::MaterialMesh2dBundle};
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::{EntityCommands, SystemParam},
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::MaterialMesh2dBundle,
+    utils::BoxedFuture,
+};
 use bevy_rapier2d::prelude::*;
 use rand::Rng;
+use serde::Deserialize;
 
-use super::StickyMaterial;
-
-const DAMPING: Damping = Damping {
-    linear_damping: 0.2,
-    angular_damping: 0.2,
+use super::{
+    balloon::{Balloon, BalloonDefinition, Sharp},
+    bomb::{Bomb, BombDefinition},
+    fracture::FractureDefinition,
+    magnet::{Magnet, MagnetDefinition},
+    outline::{outline_collider, Outlines},
+    physics::{item_colliders, CollisionLayer},
+    randomizer::Randomizer,
+    spawning::{SpawnContext, SpawnTable, SpawnTables},
+    stickiness::{Stickiness, StickinessPolicy},
+    StickyMaterial,
 };
+use crate::utils::EntityCommandsExt;
 
-pub fn orange<'w, 's, 'a>(
-    commands: &'a mut Commands<'w, 's>,
-    asset_server: &AssetServer,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    custom_materials: &mut ResMut<Assets<StickyMaterial>>,
-    radius: f32,
-) -> EntityCommands<'w, 's, 'a> {
-    let mut cmds = commands.spawn_empty();
-    cmds.with_children(|f| {
-        f.spawn((
-            ActiveEvents::COLLISION_EVENTS,
-            ActiveHooks::FILTER_CONTACT_PAIRS,
-            Collider::ball(radius),
-            Restitution::coefficient(0.8),
-            ColliderMassProperties::Density(1.05),
-            TransformBundle::from(Transform::from_xyz(0., -radius * 0.3, 0.)),
-        ));
-    })
-    .insert((
-        RigidBody::Dynamic,
-        Ccd::enabled(),
-        DAMPING,
-        MaterialMesh2dBundle {
-            mesh: meshes
-                .add(Mesh::from(shape::Quad::new(Vec2::new(2., 3.) * radius)))
-                .into(),
-            material: custom_materials.add(StickyMaterial {
-                color: Color::LIME_GREEN,
-                color_texture: asset_server.load("orange.png"),
-                sticky: 0,
-            }),
-            ..default()
-        },
-    ));
-    cmds
+pub const CATALOG: &str = "items/default.catalog.ron";
+
+/// Describes a throwable item. Loaded from `.item.ron` files.
+///
+/// All lengths are relative to the radius the item is spawned with.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "3c7a1e4b-92d5-4f0e-b6a8-5d1f2c9e7a40"]
+pub struct ItemDefinition {
+    pub name: String,
+    pub sprite: String,
+    pub radius: f32,
+    pub mesh_size: (f32, f32),
+    pub colliders: Vec<ColliderDefinition>,
+    #[serde(default)]
+    pub damping: DampingDefinition,
+    #[serde(default)]
+    pub stickiness: StickinessPolicy,
+    /// Makes the item break into fragments under strong enough impact
+    #[serde(default)]
+    pub fracture: Option<FractureDefinition>,
+    /// Makes the item explode
+    #[serde(default)]
+    pub bomb: Option<BombDefinition>,
+    /// Makes the item pull other items towards it
+    #[serde(default)]
+    pub magnet: Option<MagnetDefinition>,
+    /// Makes the item float up and lift what it's stuck to
+    #[serde(default)]
+    pub balloon: Option<BalloonDefinition>,
 }
 
-pub fn cereal_box<'w, 's, 'a>(
-    commands: &'a mut Commands<'w, 's>,
-    asset_server: &AssetServer,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    custom_materials: &mut ResMut<Assets<StickyMaterial>>,
-    radius: f32,
-) -> EntityCommands<'w, 's, 'a> {
-    let mut cmds = commands.spawn_empty();
-    cmds.insert((
-        RigidBody::Dynamic,
-        ActiveEvents::COLLISION_EVENTS,
-        ActiveHooks::FILTER_CONTACT_PAIRS,
-        Collider::cuboid(0.75 * radius, radius),
-        Restitution::coefficient(0.6),
-        ColliderMassProperties::Density(0.45),
-        Ccd::enabled(),
-        DAMPING,
-        MaterialMesh2dBundle {
-            mesh: meshes
-                .add(Mesh::from(shape::Quad::new(
-                    Vec2::new(0.75 * 2., 2.) * radius,
-                )))
-                .into(),
-            material: custom_materials.add(StickyMaterial {
-                color: Color::LIME_GREEN,
-                color_texture: asset_server.load("cereal.png"),
-                sticky: 0,
-            }),
-            ..default()
-        },
-    ));
-    cmds
+#[derive(Debug, Deserialize)]
+pub struct ColliderDefinition {
+    pub shape: ShapeDefinition,
+    #[serde(default)]
+    pub offset: (f32, f32),
+    pub restitution: f32,
+    pub density: f32,
+    /// Pops balloons
+    #[serde(default)]
+    pub sharp: bool,
 }
 
-pub fn hammer<'w, 's, 'a>(
-    commands: &'a mut Commands<'w, 's>,
-    asset_server: &AssetServer,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    custom_materials: &mut ResMut<Assets<StickyMaterial>>,
-    radius: f32,
-) -> EntityCommands<'w, 's, 'a> {
-    let mut cmds = commands.spawn_empty();
-    let handle_thickness = radius * 0.2;
-    let head_thickness = radius * 0.25;
-    let head_length = radius * 0.75;
-    cmds.with_children(|children| {
-        children.spawn((
-            Restitution::coefficient(0.2),
-            ActiveEvents::COLLISION_EVENTS,
-            ActiveHooks::FILTER_CONTACT_PAIRS,
-            Collider::cuboid(head_length, head_thickness),
-            ColliderMassProperties::Density(3.5),
-            TransformBundle::from(Transform::from_xyz(0., radius - radius * 0.3, 0.)),
-        ));
-        children.spawn((
-            Restitution::coefficient(0.5),
-            ActiveEvents::COLLISION_EVENTS,
-            ActiveHooks::FILTER_CONTACT_PAIRS,
-            Collider::cuboid(handle_thickness, radius - 0.5 * head_thickness),
-            ColliderMassProperties::Density(0.8),
-            TransformBundle::from(Transform::from_xyz(
-                0.0,
-                -0.5 * head_thickness - radius * 0.3,
-                0.,
-            )),
-        ));
-    })
-    .insert((
-        RigidBody::Dynamic,
-        Ccd::enabled(),
-        DAMPING,
-        MaterialMesh2dBundle {
-            mesh: meshes
-                .add(Mesh::from(shape::Quad::new(Vec2::new(2., 3.) * radius)))
-                .into(),
-            material: custom_materials.add(StickyMaterial {
-                color: Color::LIME_GREEN,
-                color_texture: asset_server.load("hammer.png"),
-                sticky: 0,
-            }),
-            ..default()
-        },
-    ));
-    cmds
+#[derive(Debug, Deserialize)]
+pub enum ShapeDefinition {
+    Ball(f32),
+    Cuboid(f32, f32),
+    /// Closed outline which is decomposed into convex parts
+    Polygon(Vec<(f32, f32)>),
+    /// Outline traced from the alpha channel of the sprite
+    Outline,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DampingDefinition {
+    pub linear: f32,
+    pub angular: f32,
+}
+
+impl Default for DampingDefinition {
+    fn default() -> Self {
+        Self {
+            linear: 0.2,
+            angular: 0.2,
+        }
+    }
+}
+
+impl ShapeDefinition {
+    /// Outline is needed only for [`ShapeDefinition::Outline`]
+    pub fn collider(&self, radius: f32, sprite_size: Vec2, outline: Option<&[Vec2]>) -> Collider {
+        match self {
+            ShapeDefinition::Ball(r) => Collider::ball(r * radius),
+            ShapeDefinition::Cuboid(hx, hy) => Collider::cuboid(hx * radius, hy * radius),
+            ShapeDefinition::Polygon(vertices) => {
+                let points = vertices
+                    .iter()
+                    .map(|&(x, y)| Vec2::new(x, y) * radius)
+                    .collect::<Vec<_>>();
+                let n = points.len() as u32;
+                let indices = (0..n).map(|i| [i, (i + 1) % n]).collect::<Vec<_>>();
+                Collider::convex_decomposition(&points, &indices)
+            }
+            ShapeDefinition::Outline => match outline {
+                Some(outline) => outline_collider(outline, sprite_size),
+                // Sprite hasn't been traced yet
+                None => Collider::cuboid(0.5 * sprite_size.x, 0.5 * sprite_size.y),
+            },
+        }
+    }
 }
 
-pub fn shoe<'w, 's, 'a>(
+impl ItemDefinition {
+    pub fn sprite_size(&self, radius: f32) -> Vec2 {
+        Vec2::new(self.mesh_size.0, self.mesh_size.1) * radius
+    }
+
+    /// Does any of the colliders need the outline of the sprite
+    pub fn traced(&self) -> bool {
+        self.colliders
+            .iter()
+            .any(|c| matches!(c.shape, ShapeDefinition::Outline))
+    }
+}
+
+impl ColliderDefinition {
+    fn offset(&self, radius: f32) -> Vec2 {
+        Vec2::new(self.offset.0, self.offset.1) * radius
+    }
+}
+
+/// List of items that can be spawned. Loaded from `.catalog.ron` files.
+#[derive(Debug, TypeUuid)]
+#[uuid = "a41d6f0c-7b3e-4c58-9e21-0f8b6d3c5a17"]
+pub struct ItemCatalog {
+    pub items: Vec<Handle<ItemDefinition>>,
+}
+
+#[derive(Deserialize)]
+struct CatalogFile {
+    items: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct ItemDefinitionLoader;
+
+impl AssetLoader for ItemDefinitionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definition = ron::de::from_bytes::<ItemDefinition>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definition));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["item.ron"]
+    }
+}
+
+#[derive(Default)]
+pub struct ItemCatalogLoader;
+
+impl AssetLoader for ItemCatalogLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file = ron::de::from_bytes::<CatalogFile>(bytes)?;
+            let items = file
+                .items
+                .iter()
+                .map(|path| load_context.get_handle(path.as_str()))
+                .collect();
+            let dependencies = file.items.into_iter().map(Into::into).collect();
+            load_context.set_default_asset(
+                LoadedAsset::new(ItemCatalog { items }).with_dependencies(dependencies),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["catalog.ron"]
+    }
+}
+
+/// Catalog the game spawns items from
+#[derive(Resource)]
+pub struct Items {
+    pub catalog: Handle<ItemCatalog>,
+}
+
+pub fn load_items(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Items {
+        catalog: asset_server.load(CATALOG),
+    });
+}
+
+#[derive(SystemParam)]
+pub struct ItemAssets<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    items: Res<'w, Items>,
+    catalogs: Res<'w, Assets<ItemCatalog>>,
+    definitions: Res<'w, Assets<ItemDefinition>>,
+    outlines: Res<'w, Outlines>,
+    tables: Res<'w, SpawnTables>,
+    spawn_tables: Res<'w, Assets<SpawnTable>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> ItemAssets<'w, 's> {
+    pub fn catalog(&self) -> Option<&ItemCatalog> {
+        self.catalogs.get(&self.items.catalog)
+    }
+
+    pub fn tables(&self) -> &SpawnTables {
+        &self.tables
+    }
+
+    pub fn spawn_table(&self, handle: &Handle<SpawnTable>) -> Option<&SpawnTable> {
+        self.spawn_tables.get(handle)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        let tables = [&self.tables.queue, &self.tables.drops];
+        if !tables.iter().all(|table| self.spawn_tables.contains(table)) {
+            return false;
+        }
+        self.catalog().is_some_and(|catalog| {
+            catalog.items.iter().all(|item| {
+                self.definition(item).is_some_and(|definition| {
+                    !definition.traced() || self.outline(definition).is_some()
+                })
+            })
+        })
+    }
+
+    pub fn outline(&self, definition: &ItemDefinition) -> Option<&[Vec2]> {
+        self.outlines
+            .get(&self.asset_server.get_handle(definition.sprite.as_str()))
+    }
+
+    pub fn definition(&self, handle: &Handle<ItemDefinition>) -> Option<&ItemDefinition> {
+        self.definitions.get(handle)
+    }
+
+    pub fn random<R: Rng>(&self, rng: &mut R) -> &Handle<ItemDefinition> {
+        let catalog = self.catalog().expect("Item catalog should be loaded");
+        &catalog.items[rng.gen_range(0..catalog.items.len())]
+    }
+
+    /// Picks item and its radius from the table.
+    ///
+    /// Falls back to any item if nothing in the table is allowed yet.
+    pub fn choose<R: Rng>(
+        &self,
+        rng: &mut R,
+        randomizer: &mut dyn Randomizer,
+        table: &Handle<SpawnTable>,
+        context: &SpawnContext,
+    ) -> (&Handle<ItemDefinition>, f32) {
+        let chosen = self
+            .spawn_table(table)
+            .and_then(|table| table.choose(randomizer, context))
+            .and_then(|entry| Some((self.by_name(&entry.item)?, entry)));
+        let (handle, radius) = match chosen {
+            Some((handle, entry)) => (handle, entry.radius(rng)),
+            None => (self.random(rng), None),
+        };
+        let radius = radius
+            .or_else(|| self.definition(handle).map(|d| d.radius))
+            .unwrap_or(50.);
+        (handle, radius)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Handle<ItemDefinition>> {
+        self.catalog()?.items.iter().find(|item| {
+            self.definition(item)
+                .is_some_and(|definition| definition.name == name)
+        })
+    }
+}
+
+/// Which definition the item was spawned from
+#[derive(Component, Clone, Debug)]
+pub struct Item {
+    pub definition: Handle<ItemDefinition>,
+    pub radius: f32,
+}
+
+/// Index of the collider definition the collider was created from
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ItemCollider(pub usize);
+
+pub fn spawn_item<'w, 's, 'a>(
     commands: &'a mut Commands<'w, 's>,
     asset_server: &AssetServer,
     meshes: &mut ResMut<Assets<Mesh>>,
     custom_materials: &mut ResMut<Assets<StickyMaterial>>,
+    items: &ItemAssets,
+    handle: &Handle<ItemDefinition>,
     radius: f32,
+    layer: CollisionLayer,
 ) -> EntityCommands<'w, 's, 'a> {
-    let mid = Vec2::new(radius, radius);
+    let definition = items
+        .definition(handle)
+        .expect("Item definition should be loaded before spawning");
+    let sprite_size = definition.sprite_size(radius);
+    let outline = items.outline(definition);
+    // Breakable items need to know how hard they are hit
+    let (events, threshold) = match &definition.fracture {
+        Some(fracture) => (
+            ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS,
+            fracture.threshold,
+        ),
+        None => (ActiveEvents::COLLISION_EVENTS, f32::MAX),
+    };
+    let groups = layer.groups();
     let mut cmds = commands.spawn_empty();
-
+    match &definition.colliders[..] {
+        [collider] if collider.offset == (0., 0.) => {
+            cmds.insert((
+                events,
+                ContactForceEventThreshold(threshold),
+                ActiveHooks::FILTER_CONTACT_PAIRS,
+                groups,
+                collider.shape.collider(radius, sprite_size, outline),
+                Restitution::coefficient(collider.restitution),
+                ColliderMassProperties::Density(collider.density),
+                ItemCollider(0),
+            ))
+            .maybe_insert(collider.sharp.then_some(Sharp));
+        }
+        colliders => {
+            cmds.with_children(|children| {
+                for (i, collider) in colliders.iter().enumerate() {
+                    let offset = collider.offset(radius);
+                    children
+                        .spawn((
+                            events,
+                            ContactForceEventThreshold(threshold),
+                            ActiveHooks::FILTER_CONTACT_PAIRS,
+                            groups,
+                            collider.shape.collider(radius, sprite_size, outline),
+                            Restitution::coefficient(collider.restitution),
+                            ColliderMassProperties::Density(collider.density),
+                            TransformBundle::from(Transform::from_xyz(offset.x, offset.y, 0.)),
+                            ItemCollider(i),
+                        ))
+                        .maybe_insert(collider.sharp.then_some(Sharp));
+                }
+            });
+        }
+    }
     cmds.insert((
         RigidBody::Dynamic,
-        ActiveEvents::COLLISION_EVENTS,
-        ActiveHooks::FILTER_CONTACT_PAIRS,
-        Collider::convex_decomposition(
-            &[
-                Vec2::new(0., 0.) - mid,
-                Vec2::new(0., 2. * radius) - mid,
-                Vec2::new(radius, 2. * radius) - mid,
-                Vec2::new(radius, 0.75 * radius) - mid,
-                Vec2::new(2. * radius, 0.75 * radius) - mid,
-                Vec2::new(2. * radius, 0.) - mid,
-            ],
-            &[[0, 1], [1, 2], [2, 3], [3, 4], [4, 5], [5, 0]],
-        ),
-        Restitution::coefficient(1.),
-        ColliderMassProperties::Density(1.15),
         Ccd::enabled(),
-        DAMPING,
+        Velocity::zero(),
+        ReadMassProperties::default(),
+        // Blasts and magnets use these instead of inserting their own
+        ExternalImpulse::default(),
+        ExternalForce::default(),
+        Damping {
+            linear_damping: definition.damping.linear,
+            angular_damping: definition.damping.angular,
+        },
         MaterialMesh2dBundle {
-            mesh: meshes
-                .add(Mesh::from(shape::Quad::new(Vec2::new(2., 2.) * radius)))
-                .into(),
+            mesh: meshes.add(Mesh::from(shape::Quad::new(sprite_size))).into(),
             material: custom_materials.add(StickyMaterial {
                 color: Color::LIME_GREEN,
-                color_texture: asset_server.load("boot.png"),
-                sticky: 0,
+                color_texture: asset_server.load(definition.sprite.as_str()),
+                sticky: (definition.stickiness == StickinessPolicy::Always) as i32,
+                policy: definition.stickiness.shader_index(),
+                remaining: 1.,
             }),
             ..default()
         },
+        Item {
+            definition: handle.clone(),
+            radius,
+        },
+        Stickiness::new(definition.stickiness),
+        layer,
     ));
+    if let Some(bomb) = &definition.bomb {
+        cmds.insert(Bomb::new(bomb));
+    }
+    if let Some(magnet) = &definition.magnet {
+        cmds.insert(Magnet::new(magnet));
+    }
+    if let Some(balloon) = &definition.balloon {
+        cmds.insert(Balloon::new(balloon));
+    }
     cmds
 }
 
 pub fn random_item<'w, 's, 'a, R>(
     rng: &mut R,
+    randomizer: &mut dyn Randomizer,
+    table: &Handle<SpawnTable>,
+    context: &SpawnContext,
     commands: &'a mut Commands<'w, 's>,
     asset_server: &'a AssetServer,
     meshes: &mut ResMut<Assets<Mesh>>,
     custom_materials: &mut ResMut<Assets<StickyMaterial>>,
+    items: &ItemAssets,
+    layer: CollisionLayer,
 ) -> EntityCommands<'w, 's, 'a>
 where
     R: Rng,
 {
-    match rng.gen_range(0..=3) {
-        0 => shoe(commands, asset_server, meshes, custom_materials, 50.),
-        1 => orange(commands, asset_server, meshes, custom_materials, 50.),
-        2 => cereal_box(commands, asset_server, meshes, custom_materials, 75.),
-        3 => hammer(commands, asset_server, meshes, custom_materials, 50.),
-        _ => unreachable!(),
+    let (handle, radius) = items.choose(rng, randomizer, table, context);
+    spawn_item(
+        commands,
+        asset_server,
+        meshes,
+        custom_materials,
+        items,
+        handle,
+        radius,
+        layer,
+    )
+}
+
+/// Applies changed item definitions to already spawned items, both thrown and queued ones.
+///
+/// Changing the amount of colliders requires respawning the item, so only new items get those.
+pub fn handle_item_reloading(
+    mut events: EventReader<AssetEvent<ItemDefinition>>,
+    definitions: ItemAssets,
+    mut items: Query<(&Item, &mut Damping, Option<&Children>, Entity)>,
+    mut colliders: Query<(
+        &ItemCollider,
+        &mut Collider,
+        &mut Restitution,
+        &mut ColliderMassProperties,
+        &mut Transform,
+    )>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if let Some(definition) = definitions.definition(handle) {
+                let outline = definitions.outline(definition);
+                info!("Reloaded item definition {}", definition.name);
+                for (item, mut damping, children, entity) in items.iter_mut() {
+                    if &item.definition != handle {
+                        continue;
+                    }
+                    damping.linear_damping = definition.damping.linear;
+                    damping.angular_damping = definition.damping.angular;
+
+                    for part in item_colliders(entity, children) {
+                        if let Ok((
+                            &ItemCollider(i),
+                            mut collider,
+                            mut restitution,
+                            mut mass,
+                            mut transform,
+                        )) = colliders.get_mut(part)
+                        {
+                            if let Some(def) = definition.colliders.get(i) {
+                                *collider = def.shape.collider(
+                                    item.radius,
+                                    definition.sprite_size(item.radius),
+                                    outline,
+                                );
+                                restitution.coefficient = def.restitution;
+                                *mass = ColliderMassProperties::Density(def.density);
+                                // Collider on the body itself is never offset
+                                if part != entity {
+                                    let offset = def.offset(item.radius);
+                                    transform.translation.x = offset.x;
+                                    transform.translation.y = offset.y;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
 }
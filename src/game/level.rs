@@ -0,0 +1,97 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{fields::FieldDefinition, platform::PlatformDefinition};
+
+/// The arena is the only level for now
+pub const ARENA: &str = "levels/arena.level.ron";
+
+/// Fields and obstacles of a level. Loaded from `.level.ron` files.
+#[derive(Debug, Default, Deserialize, TypeUuid)]
+#[uuid = "3f9a1c7e-52d8-4b06-8e4d-a17b2c9f6e30"]
+pub struct Level {
+    #[serde(default)]
+    pub fields: Vec<FieldDefinition>,
+    #[serde(default)]
+    pub platforms: Vec<PlatformDefinition>,
+}
+
+impl Level {
+    /// Rejects definitions that can't be simulated
+    pub fn validate(&self) -> Result<(), String> {
+        match self
+            .platforms
+            .iter()
+            .find(|platform| !platform.path.is_valid())
+        {
+            Some(platform) => Err(format!(
+                "platform at {:?} needs a positive duration or period, got {:?}",
+                platform.position, platform.path
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let level = ron::de::from_bytes::<Level>(bytes)?;
+            level.validate().map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// Level being played
+#[derive(Resource)]
+pub struct CurrentLevel(pub Handle<Level>);
+
+pub fn load_level(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CurrentLevel(asset_server.load(ARENA)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_paths_need_positive_time() {
+        let level = |path: &str| {
+            ron::de::from_str::<Level>(&format!(
+                "(platforms: [(size: (50., 10.), position: (0., 0.), path: {path})])"
+            ))
+            .unwrap()
+        };
+        assert!(level("Still").validate().is_ok());
+        assert!(level("Linear(offset: (100., 0.), duration: 2.)")
+            .validate()
+            .is_ok());
+        assert!(level("Linear(offset: (100., 0.), duration: 0.)")
+            .validate()
+            .is_err());
+        assert!(level("PingPong(offset: (100., 0.), duration: -1.)")
+            .validate()
+            .is_err());
+        assert!(level("Circular(radius: 100., period: 0.)")
+            .validate()
+            .is_err());
+    }
+}
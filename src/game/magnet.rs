@@ -0,0 +1,64 @@
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use super::throw::{IgnoreCollisions, Throwable};
+
+#[derive(Debug, Deserialize)]
+pub struct MagnetDefinition {
+    /// Distance the magnet reaches
+    pub radius: f32,
+    /// Force applied right next to the magnet. Falls off linearly to zero at the radius.
+    pub strength: f32,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Magnet {
+    pub radius: f32,
+    pub strength: f32,
+}
+
+impl Magnet {
+    pub fn new(definition: &MagnetDefinition) -> Self {
+        Self {
+            radius: definition.radius,
+            strength: definition.strength,
+        }
+    }
+}
+
+/// Pulls throwables towards magnets.
+///
+/// Magnet is pulled back just as hard, so items glued to the magnet don't drag their cluster around.
+pub fn handle_magnets(
+    // Queued and held magnets don't pull anything yet
+    magnets: Query<(&Magnet, &GlobalTransform, Entity), Without<IgnoreCollisions>>,
+    mut throwables: Query<
+        (&GlobalTransform, &mut ExternalForce, Entity),
+        (With<Throwable>, Without<IgnoreCollisions>),
+    >,
+) {
+    let mut forces = HashMap::new();
+    for (magnet, magnet_transform, magnet_entity) in magnets.iter() {
+        let center = magnet_transform.translation().xy();
+        for (transform, _, entity) in throwables.iter() {
+            if entity == magnet_entity {
+                continue;
+            }
+            let offset = center - transform.translation().xy();
+            let distance = offset.length();
+            if distance < magnet.radius {
+                let force =
+                    offset.normalize_or_zero() * magnet.strength * (1. - distance / magnet.radius);
+                *forces.entry(entity).or_insert(Vec2::ZERO) += force;
+                *forces.entry(magnet_entity).or_insert(Vec2::ZERO) -= force;
+            }
+        }
+    }
+
+    for (entity, force) in forces {
+        if let Ok((_, mut external_force, _)) = throwables.get_mut(entity) {
+            external_force.force += force;
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::TextureFormat,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier2d::prelude::*;
+
+use super::items::ItemDefinition;
+
+/// Pixels with lower alpha are considered to be outside of the sprite
+const ALPHA_THRESHOLD: u8 = 128;
+/// How far simplified outline can stray from the traced one relative to the sprite size
+const SIMPLIFY_TOLERANCE: f32 = 0.015;
+
+/// Outlines traced from the alpha channel of sprites.
+///
+/// Outlines are normalized so that the sprite covers `[-0.5, 0.5]` on both axes.
+#[derive(Resource, Default)]
+pub struct Outlines {
+    /// Keeps the traced sprites loaded
+    sprites: HashMap<String, Handle<Image>>,
+    outlines: HashMap<Handle<Image>, Vec<Vec2>>,
+}
+
+impl Outlines {
+    pub fn get(&self, image: &Handle<Image>) -> Option<&[Vec2]> {
+        self.outlines.get(image).map(|o| &o[..])
+    }
+}
+
+/// Creates collider from normalized outline for sprite of given size
+pub fn outline_collider(outline: &[Vec2], size: Vec2) -> Collider {
+    let points = outline.iter().map(|&p| p * size).collect::<Vec<_>>();
+    let n = points.len() as u32;
+    let indices = (0..n).map(|i| [i, (i + 1) % n]).collect::<Vec<_>>();
+    Collider::convex_decomposition(&points, &indices)
+}
+
+/// Traces outlines for sprites of items that want their collider from it
+pub fn handle_outline_tracing(
+    asset_server: Res<AssetServer>,
+    definitions: Res<Assets<ItemDefinition>>,
+    images: Res<Assets<Image>>,
+    mut events: EventReader<AssetEvent<Image>>,
+    mut outlines: ResMut<Outlines>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } | AssetEvent::Removed { handle } = event {
+            outlines.outlines.remove(handle);
+        }
+    }
+
+    let sprites = definitions
+        .iter()
+        .filter(|(_, definition)| definition.traced())
+        .map(|(_, definition)| definition.sprite.as_str())
+        .collect::<HashSet<_>>();
+
+    for sprite in sprites {
+        let handle = outlines
+            .sprites
+            .entry(sprite.to_owned())
+            .or_insert_with(|| asset_server.load(sprite))
+            .clone();
+        if outlines.outlines.contains_key(&handle) {
+            continue;
+        }
+        if let Some(image) = images.get(&handle) {
+            if let Some(outline) = trace_image(image) {
+                outlines.outlines.insert(handle, outline);
+            } else {
+                warn!("Could not trace outline of {sprite}");
+            }
+        }
+    }
+}
+
+fn trace_image(image: &Image) -> Option<Vec<Vec2>> {
+    let converted;
+    let image = match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => image,
+        _ => {
+            converted = image.convert(TextureFormat::Rgba8UnormSrgb)?;
+            &converted
+        }
+    };
+    let size = image.texture_descriptor.size;
+    let (width, height) = (size.width as usize, size.height as usize);
+    trace_sprite(width, height, |x, y| {
+        image.data[4 * (y * width + x) + 3] >= ALPHA_THRESHOLD
+    })
+}
+
+/// Traces and simplifies the outline of a sprite, normalized the same way as [`Outlines`]
+pub fn trace_sprite<F>(width: usize, height: usize, inside: F) -> Option<Vec<Vec2>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let outline = trace_outline(width, height, inside)?;
+    let size = Vec2::new(width as f32, height as f32);
+    let normalized = outline
+        .into_iter()
+        .map(|p| Vec2::new(p.x / size.x - 0.5, 0.5 - p.y / size.y))
+        .collect::<Vec<_>>();
+    let simplified = simplify(&normalized, SIMPLIFY_TOLERANCE);
+    (simplified.len() >= 3).then_some(simplified)
+}
+
+/// Traces the outline enclosing the largest area marked inside using marching squares.
+///
+/// Returned points are in pixel coordinates where the center of pixel `(x, y)` is at `(x + 0.5, y + 0.5)`.
+pub fn trace_outline<F>(width: usize, height: usize, inside: F) -> Option<Vec<Vec2>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    // Sample grid is padded by one empty sample so every outline is closed
+    let sample = |x: usize, y: usize| {
+        (1..=width).contains(&x) && (1..=height).contains(&y) && inside(x - 1, y - 1)
+    };
+
+    // Points are at the middle of the cell edges, so doubled coordinates keep them integral.
+    // Ordered map makes every trace start from the same point, which seeded games rely on.
+    let mut neighbours: BTreeMap<(usize, usize), Vec<(usize, usize)>> = BTreeMap::new();
+    let mut connect = |a, b| {
+        neighbours.entry(a).or_default().push(b);
+        neighbours.entry(b).or_default().push(a);
+    };
+    for y in 0..=height {
+        for x in 0..=width {
+            let case = (sample(x, y) as u8) << 3
+                | (sample(x + 1, y) as u8) << 2
+                | (sample(x + 1, y + 1) as u8) << 1
+                | sample(x, y + 1) as u8;
+            let top = (2 * x + 1, 2 * y);
+            let right = (2 * x + 2, 2 * y + 1);
+            let bottom = (2 * x + 1, 2 * y + 2);
+            let left = (2 * x, 2 * y + 1);
+            match case {
+                1 | 14 => connect(left, bottom),
+                2 | 13 => connect(bottom, right),
+                3 | 12 => connect(left, right),
+                4 | 11 => connect(top, right),
+                6 | 9 => connect(top, bottom),
+                7 | 8 => connect(top, left),
+                // Saddles are resolved by keeping the inside corners apart
+                5 => {
+                    connect(top, right);
+                    connect(left, bottom);
+                }
+                10 => {
+                    connect(top, left);
+                    connect(bottom, right);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut largest: Option<Vec<Vec2>> = None;
+    for &start in neighbours.keys() {
+        if visited.contains(&start) {
+            continue;
+        }
+        let mut outline = vec![];
+        let mut prev = start;
+        let mut cur = start;
+        loop {
+            visited.insert(cur);
+            outline.push(Vec2::new(cur.0 as f32, cur.1 as f32) * 0.5 - Vec2::splat(0.5));
+            let next = neighbours[&cur]
+                .iter()
+                .copied()
+                .find(|&n| n != prev && !visited.contains(&n));
+            match next {
+                Some(next) => {
+                    prev = cur;
+                    cur = next;
+                }
+                None => break,
+            }
+        }
+        if largest.as_ref().is_none_or(|l| area(l) < area(&outline)) {
+            largest = Some(outline);
+        }
+    }
+    largest
+}
+
+fn area(outline: &[Vec2]) -> f32 {
+    let n = outline.len();
+    (0..n)
+        .map(|i| outline[i].perp_dot(outline[(i + 1) % n]))
+        .sum::<f32>()
+        .abs()
+        * 0.5
+}
+
+/// Simplifies closed outline with Ramer-Douglas-Peucker
+pub fn simplify(outline: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if outline.len() < 3 {
+        return outline.to_vec();
+    }
+    // Split the loop at the point furthest from the first one and simplify both halves
+    let far = (1..outline.len())
+        .max_by(|&a, &b| {
+            let da = outline[a].distance_squared(outline[0]);
+            let db = outline[b].distance_squared(outline[0]);
+            da.total_cmp(&db)
+        })
+        .unwrap();
+    let mut first = simplify_open(&outline[..=far], tolerance);
+    let mut second = outline[far..].to_vec();
+    second.push(outline[0]);
+    let second = simplify_open(&second, tolerance);
+    first.pop();
+    first.extend_from_slice(&second[..second.len() - 1]);
+    first
+}
+
+fn simplify_open(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let distance = |p: Vec2| {
+        let line = last - first;
+        if line.length_squared() == 0. {
+            p.distance(first)
+        } else {
+            (line.perp_dot(p - first)).abs() / line.length()
+        }
+    };
+    let furthest = (1..points.len().saturating_sub(1))
+        .map(|i| (i, distance(points[i])))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    match furthest {
+        Some((i, d)) if d > tolerance => {
+            let mut result = simplify_open(&points[..=i], tolerance);
+            result.pop();
+            result.extend(simplify_open(&points[i..], tolerance));
+            result
+        }
+        _ => vec![first, last],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Traces a bitmap drawn with `#` for the pixels inside
+    fn trace(rows: &[&str]) -> Option<Vec<Vec2>> {
+        let (width, height) = (rows[0].len(), rows.len());
+        trace_outline(width, height, |x, y| rows[y].as_bytes()[x] == b'#')
+    }
+
+    fn bounds(outline: &[Vec2]) -> (Vec2, Vec2) {
+        let min = outline.iter().copied().reduce(Vec2::min).unwrap();
+        let max = outline.iter().copied().reduce(Vec2::max).unwrap();
+        (min, max)
+    }
+
+    fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+        let t = ((p - a).dot(b - a) / (b - a).length_squared()).clamp(0., 1.);
+        p.distance(a + t * (b - a))
+    }
+
+    #[test]
+    fn filled_square() {
+        let outline = trace(&["......", ".####.", ".####.", ".####.", ".####.", "......"]).unwrap();
+        // Outline runs between the centres of inside and outside pixels, cutting the corners
+        assert_eq!(bounds(&outline), (Vec2::splat(1.), Vec2::splat(5.)));
+        assert_eq!(area(&outline), 16. - 4. * 0.125);
+    }
+
+    #[test]
+    fn largest_blob_wins() {
+        let outline = trace(&["........", ".##.###.", ".##.###.", "....###.", "........"]).unwrap();
+        assert_eq!(bounds(&outline), (Vec2::new(4., 1.), Vec2::new(7., 4.)));
+    }
+
+    #[test]
+    fn holes_are_left_out() {
+        let outline = trace(&["......", ".####.", ".#..#.", ".#..#.", ".####.", "......"]).unwrap();
+        // Outer edge encloses more than the edge of the hole
+        assert_eq!(bounds(&outline), (Vec2::splat(1.), Vec2::splat(5.)));
+    }
+
+    #[test]
+    fn nothing_to_trace() {
+        assert_eq!(trace(&["...", "..."]), None);
+    }
+
+    #[test]
+    fn simplify_keeps_endpoints_and_tolerance() {
+        let circle = (0..100)
+            .map(|i| Vec2::from_angle(i as f32 / 100. * std::f32::consts::TAU))
+            .collect::<Vec<_>>();
+        let tolerance = 0.01;
+        let simplified = simplify(&circle, tolerance);
+        assert!(simplified.len() >= 3 && simplified.len() < circle.len());
+        assert_eq!(simplified[0], circle[0]);
+        for p in &circle {
+            let n = simplified.len();
+            let distance = (0..n)
+                .map(|i| distance_to_segment(*p, simplified[i], simplified[(i + 1) % n]))
+                .fold(f32::MAX, f32::min);
+            assert!(distance <= tolerance, "{p} is {distance} away");
+        }
+
+        let line = [Vec2::ZERO, Vec2::new(1., 0.001), Vec2::new(2., 0.)];
+        assert_eq!(simplify_open(&line, tolerance), vec![line[0], line[2]]);
+        let corner = [Vec2::ZERO, Vec2::new(1., 1.), Vec2::new(2., 0.)];
+        assert_eq!(simplify_open(&corner, tolerance), corner.to_vec());
+    }
+}
@@ -1,150 +1,192 @@
-use std::convert::identity;
-use std::sync::Mutex;
-
 use bevy::ecs::query::WorldQuery;
 use bevy::math::Vec3Swizzles;
-use bevy::{prelude::*, utils::HashMap};
+use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy_rapier2d::rapier::prelude::CollisionEventFlags;
-use union_find::Union;
-use union_find::UnionFind;
-use union_find::{QuickFindUf, UnionBySizeRank};
 
 use crate::game::{DeathTimer, Destroyer, ScoringEffect, Wall};
 
-use super::throw::{Ghost, IgnoreCollisions, Player, Throwable};
+use super::cluster::{ClusterId, StuckItems};
+use super::dome::Dome;
+use super::platform::Platform;
+use super::stickiness::{Stickiness, StickinessPolicy};
+use super::throw::{Player, Throwable};
 use super::Disabler;
 
-// TODO: When entities are deleted clear this data structure
-#[derive(Component)]
-pub struct StuckItems {
-    // TODO: Had to wrap this to mutex because `find` borrows mutably (because QuickUnionUf modifies the data structure)
-    // and `PhysicsHooksWithQuery` allows only immutable queries :/
-    pub union_find: Mutex<QuickFindUf<EntityWrapper>>,
-    pub map: HashMap<Entity, usize>,
-}
-impl StuckItems {
-    fn add_entity(&mut self, e: Entity) -> usize {
-        *self.map.entry(e).or_insert_with(|| {
-            self.union_find
-                .lock()
-                .unwrap()
-                .insert(EntityWrapper(e, default()))
-        })
+/// Despawns the item and lets go of everything glued to it
+pub fn despawn_item(
+    commands: &mut Commands,
+    entity: Entity,
+    impulse_joints: &Query<(&ImpulseJoint, &Parent, Entity)>,
+    stuck_items: &mut StuckItems,
+    players: &mut Query<&mut Player>,
+) {
+    // Joints owned by the item go with it, but the ones pointing at it are on other items
+    for (joint, _, joint_entity) in impulse_joints.iter() {
+        if joint.parent == entity {
+            commands.entity(joint_entity).despawn_recursive();
+        }
     }
-    fn union(&self, key0: usize, key1: usize) {
-        self.union_find.lock().unwrap().union(key0, key1);
+    stuck_items.remove_entity(entity);
+    for mut player in players.iter_mut() {
+        player.disables.remove(&entity);
     }
+    commands.entity(entity).despawn_recursive();
+}
+
+/// Body the collider belongs to, as colliders are on the body itself or its direct children
+pub fn body_of(collider: Entity, parent: Option<&Parent>) -> Entity {
+    parent.map_or(collider, |parent| parent.get())
+}
+
+/// Entities that can hold colliders of the body, which are the body itself and its direct children
+pub fn item_colliders(
+    entity: Entity,
+    children: Option<&Children>,
+) -> impl Iterator<Item = Entity> + '_ {
+    Some(entity)
+        .into_iter()
+        .chain(children.into_iter().flatten().copied())
 }
 
-// TODO: `UnionBySizeRank` chosen for no particular reason. `UnionBySizeRank` is broken https://github.com/gifnksm/union-find-rs/issues/12
-pub struct EntityWrapper(Entity, UnionBySizeRank);
+/// Impulse glue joint can take in total before breaking
+const GLUE_STRENGTH: f32 = 20.;
+/// Force glue joint can hold indefinitely
+const GLUE_HOLD: f32 = 30.;
 
-impl Union for EntityWrapper {
-    fn union(lval: Self, rval: Self) -> union_find::UnionResult<Self> {
-        use union_find::UnionResult::*;
-        match UnionBySizeRank::union(lval.1, rval.1) {
-            Left(l) => Left(EntityWrapper(lval.0, l)),
-            Right(r) => Right(EntityWrapper(rval.0, r)),
+/// Budget of a glue joint. Drops while the joint carries more than it can hold.
+#[derive(Component, Clone, Debug)]
+pub struct Glue {
+    pub budget: f32,
+}
+
+impl Default for Glue {
+    fn default() -> Self {
+        Self {
+            budget: GLUE_STRENGTH,
         }
     }
 }
 
+/// Pixels in a meter of the physics world.
+///
+/// The physics plugin is built from this, and everything converting rapier coordinates to pixels
+/// reads it back from [`RapierContext::physics_scale`].
+#[derive(Clone, Copy, Debug)]
+pub struct PhysicsScale(pub f32);
+
+impl Default for PhysicsScale {
+    fn default() -> Self {
+        Self(100.)
+    }
+}
+
+impl PhysicsScale {
+    pub fn plugin(self) -> RapierPhysicsPlugin<PhysicsData<'static>> {
+        RapierPhysicsPlugin::pixels_per_meter(self.0)
+    }
+}
+
 pub struct Hooks;
 
-#[derive(WorldQuery)]
-#[world_query(derive(Default))]
-pub struct PhysicsData<'a> {
-    joint: Option<&'a ImpulseJoint>,
-    ghost: Option<&'a Ghost>,
-    parent: Option<&'a Parent>,
-    items: Option<&'a StuckItems>,
-    ignore_collisions: Option<&'a IgnoreCollisions>,
+/// Walls, floor and other static parts of the level
+const WORLD_GROUP: Group = Group::GROUP_1;
+/// Thrown, dropped and stuck items
+const ITEM_GROUP: Group = Group::GROUP_2;
+/// Queued and held items
+const HELD_GROUP: Group = Group::GROUP_3;
+const SENSOR_GROUP: Group = Group::GROUP_4;
+
+/// Collision layer of the colliders of a body.
+///
+/// Glued items stay on the item layer and [`Hooks`] keeps items of the same cluster from colliding.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionLayer {
+    World,
+    Item,
+    Held,
+    Sensor,
 }
 
-fn hook_get_rec_ghost<'a, 'b>(
-    query: &'b Query<PhysicsData<'a>>,
-    e: Entity,
-) -> Option<(&'b Ghost, Entity)> {
-    let physics_data = query
-        .get(e)
-        .ok()
-        .map_or(PhysicsDataItem::default(), identity);
-    physics_data.ghost.map(|g| (g, e)).or_else(|| {
-        physics_data
-            .parent
-            .and_then(|parent| hook_get_rec_ghost(query, parent.get()))
-    })
+impl CollisionLayer {
+    /// Groups for each collider of a body on the layer
+    pub fn groups(self) -> CollisionGroups {
+        let (memberships, filters) = match self {
+            CollisionLayer::World => (WORLD_GROUP, ITEM_GROUP),
+            CollisionLayer::Item => (ITEM_GROUP, WORLD_GROUP | ITEM_GROUP | SENSOR_GROUP),
+            // Queued and held items don't touch anything until they are thrown
+            CollisionLayer::Held => (HELD_GROUP, Group::NONE),
+            CollisionLayer::Sensor => (SENSOR_GROUP, ITEM_GROUP),
+        };
+        CollisionGroups::new(memberships, filters)
+    }
 }
 
-fn hook_find_parent<'a, 'b, F>(
-    query: &'b Query<PhysicsData<'a>>,
-    e: Entity,
-    mut f: F,
-) -> Option<Entity>
-where
-    F: FnMut(Entity) -> bool,
-{
-    if f(e) {
-        return Some(e);
+/// Clears the external forces of last frame. Magnets and fields run after this and add theirs.
+pub fn reset_external_forces(mut forces: Query<&mut ExternalForce>) {
+    for mut force in forces.iter_mut() {
+        if *force != ExternalForce::default() {
+            *force = ExternalForce::default();
+        }
     }
-    let physics_data = query
-        .get(e)
-        .ok()
-        .map_or(PhysicsDataItem::default(), identity);
-
-    physics_data
-        .parent
-        .and_then(|p| hook_find_parent(query, p.get(), f))
+}
+
+/// Moves the colliders of bodies whose layer changed to the groups of the layer
+pub fn handle_collision_layers(
+    layers: Query<(&CollisionLayer, Option<&Children>, Entity), Changed<CollisionLayer>>,
+    mut colliders: Query<&mut CollisionGroups>,
+) {
+    for (&layer, children, entity) in layers.iter() {
+        let groups = layer.groups();
+        for part in item_colliders(entity, children) {
+            if let Ok(mut collision_groups) = colliders.get_mut(part) {
+                *collision_groups = groups;
+            }
+        }
+    }
+}
+
+#[derive(WorldQuery)]
+pub struct PhysicsData<'a> {
+    parent: Option<&'a Parent>,
+    cluster: Option<&'a ClusterId>,
+    dome: Option<&'a Dome>,
+    velocity: Option<&'a Velocity>,
+    transform: Option<&'a GlobalTransform>,
+}
+
+/// Whether the other collider is moving out of the dome, if the first collider is a dome
+fn passes_dome(dome: Entity, other: Entity, query: &Query<PhysicsData>) -> Option<bool> {
+    let dome = query.get(dome).ok()?;
+    dome.dome?;
+    let centre = dome.transform?.translation().xy();
+    let body = query
+        .get(body_of(other, query.get(other).ok()?.parent))
+        .ok()?;
+    let position = body.transform?.translation().xy();
+    Some(Dome::lets_through(centre, position, body.velocity?.linvel))
 }
 
 impl<'a> PhysicsHooksWithQuery<PhysicsData<'a>> for Hooks {
+    /// Collision layers filter everything else, so only glued items of the same cluster and items
+    /// leaving the dome are left
     fn filter_contact_pair(
         &self,
         context: PairFilterContextView,
         query: &Query<PhysicsData<'a>>,
     ) -> Option<SolverFlags> {
-        let (a, b) = (context.collider1(), context.collider2());
-        let ghost_a = hook_get_rec_ghost(query, a);
-        if let Some((&Ghost(g), _)) = ghost_a {
-            if hook_find_parent(query, b, |e| g == e).is_some() {
-                return None;
-            }
-        }
-        let ghost_b = hook_get_rec_ghost(query, b);
-        if let Some((&Ghost(g), _)) = ghost_b {
-            if hook_find_parent(query, a, |e| g == e).is_some() {
-                return None;
-            }
-        }
-        if ghost_a.is_some() && ghost_b.is_some() {
-            return None;
-        }
-
-        let get_parent = |e| query.get(e).ok().and_then(|j| j.parent);
-        let get = |e| query.get(e).ok().and_then(|j| j.ignore_collisions);
-
-        if get_recursively(get_parent, get, a).is_some() {
+        let (c1, c2) = (context.collider1(), context.collider2());
+        if passes_dome(c1, c2, query).or_else(|| passes_dome(c2, c1, query)) == Some(true) {
             return None;
         }
-        if get_recursively(get_parent, get, b).is_some() {
-            return None;
-        }
-
-        let stuck_items = query.iter().flat_map(|j| j.items).next().unwrap();
-
-        let p1 = find_most_parent(get_parent, a);
-        let p2 = find_most_parent(get_parent, b);
-
-        if let (Some(ia), Some(ib)) = (stuck_items.map.get(&p1), stuck_items.map.get(&p2)) {
-            let pa = stuck_items.union_find.lock().unwrap().find(*ia);
-            let pb = stuck_items.union_find.lock().unwrap().find(*ib);
-            if pa == pb {
-                return None;
-            }
+        let cluster = |collider| {
+            let body = body_of(collider, query.get(collider).ok()?.parent);
+            query.get(body).ok()?.cluster.copied()
+        };
+        match (cluster(c1), cluster(c2)) {
+            (Some(c1), Some(c2)) if c1 == c2 => None,
+            _ => Some(SolverFlags::COMPUTE_IMPULSES),
         }
-
-        return Some(SolverFlags::COMPUTE_IMPULSES);
     }
 }
 
@@ -163,37 +205,33 @@ where
         .or_else(|| get_parent(e).and_then(|parent| get_recursively(get_parent, get, parent.get())))
 }
 
-fn find_most_parent<'a, F>(mut get_parent: F, e: Entity) -> Entity
-where
-    F: FnMut(Entity) -> Option<&'a Parent>,
-{
-    get_parent(e)
-        .map(|p| find_most_parent(get_parent, p.get()))
-        .unwrap_or(e)
-}
-
 pub fn handle_collisions(
     mut commands: Commands,
     rapier_context: Res<RapierContext>,
     mut collision_events: EventReader<CollisionEvent>,
     parents: Query<&Parent>,
     mut players: Query<&mut Player>,
-    ghosts: Query<&Ghost>,
     mut throwables: Query<&mut Throwable>,
     transforms: Query<&Transform>,
     impulse_joints: Query<&ImpulseJoint>,
     destroyers: Query<&Destroyer>,
     disablers: Query<&Disabler>,
     walls: Query<&Wall>,
+    platforms: Query<&Platform>,
     mut stuck_items: Query<&mut StuckItems>,
+    stickiness: Query<&Stickiness>,
     asset_server: Res<AssetServer>,
 ) {
     let stuck_items = &mut *stuck_items.single_mut();
+    let policy = |e| {
+        stickiness
+            .get(e)
+            .map_or(StickinessPolicy::default(), |s| s.policy)
+    };
 
     for collision_event in collision_events.iter() {
         let get_parent = |e| parents.get(e).ok();
         let get_throwable = |e| throwables.get(e).ok();
-        let get_ghost = |e| ghosts.get(e).ok();
         match collision_event {
             CollisionEvent::Started(a, b, flags) if flags.contains(CollisionEventFlags::SENSOR) => {
                 if disablers.get(*a).is_ok() {
@@ -222,18 +260,16 @@ pub fn handle_collisions(
             }
             CollisionEvent::Started(a, b, _) => {
                 let (a, b) = (*a, *b);
-                // `filter_contact_pair` should ensure that both are not ghosts or what ghost was made of
-                if let Some((_, e)) = get_recursively(get_parent, get_ghost, a) {
-                    commands.entity(e).despawn_recursive();
-                } else if let Some((_, e)) = get_recursively(get_parent, get_ghost, b) {
-                    commands.entity(e).despawn_recursive();
-                }
                 let ta = get_recursively(get_parent, get_throwable, a);
                 let tb = get_recursively(get_parent, get_throwable, b);
                 if let (Some((t1, e1)), Some((t2, e2))) = (ta, tb) {
                     if !t1.sticky && !t2.sticky {
                         continue;
                     }
+                    if !policy(e1).glues() || !policy(e2).glues() {
+                        // Slippery items slide off even sticky ones
+                        continue;
+                    }
                     if connected_by_impulse_joint(
                         (e1, impulse_joints.get(e1).ok()),
                         (e2, impulse_joints.get(e2).ok()),
@@ -242,102 +278,80 @@ pub fn handle_collisions(
                         continue;
                     }
 
-                    let get_rotation = |e| {
-                        transforms
-                            .get(e)
-                            .map(|t| t.rotation.to_euler(EulerRot::XYZ).2)
-                            .unwrap_or_default()
-                    };
-
-                    // Determine where they collided from the contact graph
-                    if let Some(contact_pair) = rapier_context.contact_pair(a, b) {
-                        if let Some((_, contact_point)) = contact_pair.find_deepest_contact() {
-                            let (e1, e2, a, b) = if a == contact_pair.collider1() {
-                                (e1, e2, a, b)
-                            } else {
-                                (e2, e1, b, a)
-                            };
-
-                            let rot1 = get_rotation(e1);
-                            let rot2 = get_rotation(e2);
-
-                            let local_transform = |mut cur, parent| {
-                                let mut transform = Vec2::ZERO;
-                                while let Ok(p) = parents.get(cur) {
-                                    if let Ok(t) = transforms.get(cur) {
-                                        transform += t.translation.xy();
-                                    }
-                                    cur = p.get();
-                                    if cur == parent {
-                                        break;
-                                    }
+                    // And then glue them together by creating fixed impulse joint between them
+                    let glued =
+                        contact_joint(&rapier_context, &parents, &transforms, (a, e1), (b, e2));
+                    if let Some((t1, joint)) = glued {
+                        commands.entity(e2).add_children(|builder| {
+                            builder.spawn((ImpulseJoint::new(e1, joint), Glue::default()));
+                        });
+
+                        stuck_items.glue(e1, e2);
+
+                        // Add score
+                        if let Ok([mut tr1, mut tr2]) = throwables.get_many_mut([e1, e2]) {
+                            let pos = transforms.get(e1).unwrap().translation.xy() + t1;
+                            let mut handle = |throwable: &mut Throwable, e| {
+                                if policy(e).spreads() {
+                                    throwable.sticky = true;
                                 }
-                                return transform;
+                                score_sticking(
+                                    &mut commands,
+                                    &asset_server,
+                                    &mut players,
+                                    throwable,
+                                    stuck_items.cluster_size(e),
+                                    pos,
+                                );
                             };
-
-                            // TODO: Get rid of hardcoded 100
-                            let t1 = local_transform(a, e1) + contact_point.local_p1() * 100.;
-                            let t2 = local_transform(b, e2) + contact_point.local_p2() * 100.;
-
-                            let (la1, lb1, la2, lb2) = (t1, -rot1, t2, -rot2);
-                            // info!("{}, {}, {}, {}", la1, lb1, la2, lb2);
-
-                            // And then glue them together by creating fixed impulse joint between them
-                            let joint = FixedJointBuilder::new()
-                                .local_anchor1(la1)
-                                .local_basis1(lb1)
-                                .local_anchor2(la2)
-                                .local_basis2(lb2);
-
-                            commands.entity(e2).add_children(|builder| {
-                                builder.spawn(ImpulseJoint::new(e1, joint));
+                            handle(&mut tr1, e1);
+                            handle(&mut tr2, e2);
+                        }
+                    }
+                } else {
+                    let riding =
+                        [(ta, a, b), (tb, b, a)]
+                            .into_iter()
+                            .find_map(|(t, collider, other)| {
+                                let (throwable, e) = t?;
+                                let rides = throwable.sticky
+                                    && policy(e).glues()
+                                    && platforms.contains(other);
+                                rides.then_some((e, collider, other))
                             });
-
-                            let i1 = stuck_items.add_entity(e1);
-                            let i2 = stuck_items.add_entity(e2);
-                            stuck_items.union(i1, i2);
-
-                            // Add score
-                            if let Ok([mut tr1, mut tr2]) = throwables.get_many_mut([e1, e2]) {
-                                let mut handle = |throwable: &mut Throwable, e| {
-                                    throwable.sticky = true;
-                                    if throwable.stuck {
-                                        return;
-                                    }
-                                    throwable.stuck = true;
-                                    if let Some(mut player) =
-                                        throwable.player.and_then(|p| players.get_mut(p).ok())
-                                    {
-                                        if let Some(&key) = stuck_items.map.get(&e) {
-                                            let size = stuck_items
-                                                .union_find
-                                                .lock()
-                                                .unwrap()
-                                                .get(key)
-                                                .1
-                                                .size();
-                                            let points = 10 * fibonacci(size);
-                                            let pos =
-                                                transforms.get(e1).unwrap().translation.xy() + t1;
-                                            let total_points = throwable.multiplier * points;
-                                            visualise_scoring(
-                                                &asset_server,
-                                                pos,
-                                                &mut commands,
-                                                points,
-                                                throwable.multiplier,
-                                                total_points,
-                                            );
-                                            player.score += total_points;
-                                        }
-                                    }
-                                };
-                                handle(&mut tr1, e1);
-                                handle(&mut tr2, e2);
+                    if let Some((e, item_collider, platform)) = riding {
+                        // Platforms hold the items stuck on them without joining their clusters
+                        if stuck_items.is_anchored_to(e, platform) {
+                            continue;
+                        }
+                        let glued = contact_joint(
+                            &rapier_context,
+                            &parents,
+                            &transforms,
+                            (platform, platform),
+                            (item_collider, e),
+                        );
+                        if let Some((t1, joint)) = glued {
+                            commands.entity(e).add_children(|builder| {
+                                builder
+                                    .spawn((ImpulseJoint::new(platform, joint), Glue::default()));
+                            });
+                            stuck_items.anchor(e, platform);
+                            if let Ok(mut throwable) = throwables.get_mut(e) {
+                                let pos = transforms.get(platform).unwrap().translation.xy() + t1;
+                                score_sticking(
+                                    &mut commands,
+                                    &asset_server,
+                                    &mut players,
+                                    &mut throwable,
+                                    stuck_items.cluster_size(e),
+                                    pos,
+                                );
                             }
                         }
+                        continue;
                     }
-                } else {
+
                     let mut destroy = |t: Option<(&Throwable, Entity)>, entity| {
                         if let Some((throwable, e)) = t {
                             if destroyers.get(entity).is_ok() {
@@ -376,10 +390,180 @@ pub fn handle_collisions(
     }
 }
 
-fn visualise_scoring(
+/// Fixed joint that holds the bodies where their colliders touch, with the anchor on the first body.
+///
+/// Colliders are the bodies themselves or their children.
+fn contact_joint(
+    rapier_context: &RapierContext,
+    parents: &Query<&Parent>,
+    transforms: &Query<&Transform>,
+    (a, e1): (Entity, Entity),
+    (b, e2): (Entity, Entity),
+) -> Option<(Vec2, FixedJointBuilder)> {
+    // Determine where they collided from the contact graph
+    let contact_pair = rapier_context.contact_pair(a, b)?;
+    let (_, contact_point) = contact_pair.find_deepest_contact()?;
+    let (p1, p2) = if a == contact_pair.collider1() {
+        (contact_point.local_p1(), contact_point.local_p2())
+    } else {
+        (contact_point.local_p2(), contact_point.local_p1())
+    };
+
+    let get_rotation = |e| {
+        transforms
+            .get(e)
+            .map(|t| t.rotation.to_euler(EulerRot::XYZ).2)
+            .unwrap_or_default()
+    };
+    let local_transform = |mut cur, parent| {
+        let mut transform = Vec2::ZERO;
+        while let Ok(p) = parents.get(cur) {
+            if let Ok(t) = transforms.get(cur) {
+                transform += t.translation.xy();
+            }
+            cur = p.get();
+            if cur == parent {
+                break;
+            }
+        }
+        transform
+    };
+
+    // Contact points are in the units of the physics world
+    let scale = rapier_context.physics_scale();
+    let t1 = local_transform(a, e1) + p1 * scale;
+    let t2 = local_transform(b, e2) + p2 * scale;
+    let joint = FixedJointBuilder::new()
+        .local_anchor1(t1)
+        .local_basis1(-get_rotation(e1))
+        .local_anchor2(t2)
+        .local_basis2(-get_rotation(e2));
+    Some((t1, joint))
+}
+
+/// Scores the item for getting stuck the first time, by the size of the cluster it got stuck in
+fn score_sticking(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    players: &mut Query<&mut Player>,
+    throwable: &mut Throwable,
+    size: usize,
+    pos: Vec2,
+) {
+    if throwable.stuck {
+        return;
+    }
+    throwable.stuck = true;
+    if let Some(mut player) = throwable.player.and_then(|p| players.get_mut(p).ok()) {
+        let points = 10 * fibonacci(size);
+        let total_points = throwable.multiplier * points;
+        visualise_scoring(
+            asset_server,
+            pos,
+            commands,
+            "",
+            points,
+            throwable.multiplier,
+            total_points,
+        );
+        player.score += total_points;
+    }
+}
+
+/// Wears glue joints down by the force they carry and breaks the worn out ones
+pub fn handle_glue_strength(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut joints: Query<(
+        &mut Glue,
+        &ImpulseJoint,
+        &RapierImpulseJointHandle,
+        &Parent,
+        Entity,
+    )>,
+    transforms: Query<&GlobalTransform>,
+    platforms: Query<&Platform>,
+    mut stuck_items: Query<&mut StuckItems>,
+    mut players: Query<&mut Player>,
+    asset_server: Res<AssetServer>,
+) {
+    let stuck_items = &mut *stuck_items.single_mut();
+    let hold = GLUE_HOLD * time.delta_seconds();
+    for (mut glue, joint, handle, owner, entity) in joints.iter_mut() {
+        if let Some(rapier_joint) = rapier_context.impulse_joints.get(handle.0) {
+            let impulse = Vec2::new(rapier_joint.impulses.x, rapier_joint.impulses.y).length();
+            glue.budget -= (impulse - hold).max(0.);
+        }
+        if glue.budget > 0. {
+            continue;
+        }
+
+        let (e1, e2) = (joint.parent, owner.get());
+        commands.entity(entity).despawn_recursive();
+        if platforms.contains(e1) {
+            // Item falls off the platform, which was never part of its cluster
+            stuck_items.unanchor(e2, e1);
+            continue;
+        }
+        let before = stuck_items.cluster_size(e1);
+        stuck_items.unglue(e1, e2);
+        let (size1, size2) = (stuck_items.cluster_size(e1), stuck_items.cluster_size(e2));
+        if size1 == before {
+            // Items are still glued together some other way
+            continue;
+        }
+
+        // Split clusters are worth less than the whole one
+        let lost = 10
+            * fibonacci(before)
+                .saturating_sub(fibonacci(size1))
+                .saturating_sub(fibonacci(size2));
+        if lost > 0 {
+            for mut player in players.iter_mut() {
+                player.score = player.score.saturating_sub(lost);
+            }
+            let pos = transforms.get(e2).map_or(Vec2::ZERO, |t| {
+                t.transform_point(joint.data.local_anchor2().extend(0.))
+                    .xy()
+            });
+            visualise_glue_break(&asset_server, pos, &mut commands, lost);
+        }
+    }
+}
+
+fn visualise_glue_break(
     asset_server: &AssetServer,
     pos: Vec2,
     commands: &mut Commands,
+    lost: usize,
+) {
+    let font = asset_server.load("fonts/MajorMonoDisplay-Regular.ttf");
+    let text_style = TextStyle {
+        font,
+        font_size: 30.0,
+        color: Color::RED,
+    };
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(format!("-{lost}"), text_style)
+                .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(pos.x, pos.y, 10.),
+            ..default()
+        })
+        .insert(ScoringEffect {
+            multiplier: 1,
+            points: lost,
+        })
+        .insert(DeathTimer(Timer::from_seconds(1., TimerMode::Once)));
+}
+
+/// Shows the points at the position. Non-empty name is shown before the points.
+pub fn visualise_scoring(
+    asset_server: &AssetServer,
+    pos: Vec2,
+    commands: &mut Commands,
+    name: &str,
     points: usize,
     multiplier: usize,
     total_points: usize,
@@ -400,10 +584,18 @@ fn visualise_scoring(
     let exclamation_marks = "!".repeat(fifties / 2);
     let question_mark = "?".repeat(fifties % 2);
     let emphasis = format!("{exclamation_marks}{question_mark}");
+    let name = if name.is_empty() {
+        "".to_owned()
+    } else {
+        format!("{name} ")
+    };
     commands
         .spawn(Text2dBundle {
-            text: Text::from_section(format!("{mult}{points}{emphasis}"), text_style.clone())
-                .with_alignment(text_alignment),
+            text: Text::from_section(
+                format!("{name}{mult}{points}{emphasis}"),
+                text_style.clone(),
+            )
+            .with_alignment(text_alignment),
             transform: Transform::from_xyz(pos.x, pos.y, 10.),
             ..default()
         })
@@ -424,6 +616,46 @@ fn connected_by_impulse_joint(
     (a, joint_a): (Entity, Option<&ImpulseJoint>),
     (b, joint_b): (Entity, Option<&ImpulseJoint>),
 ) -> bool {
-    return joint_a.map(|joint| joint.parent == b).unwrap_or(false)
-        || joint_b.map(|joint| joint.parent == a).unwrap_or(false);
+    joint_a.map(|joint| joint.parent == b).unwrap_or(false)
+        || joint_b.map(|joint| joint.parent == a).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::harness::{Harness, HALF};
+
+    /// Throws a box down on a sticky one and returns the local glue anchors of the (bottom, top) boxes
+    fn glue_anchors(scale: f32) -> (Vec2, Vec2) {
+        let mut harness = Harness::with_scale(PhysicsScale(scale));
+        let bottom = harness.drop_item(Vec2::ZERO, true);
+        harness.throw_item(Vec2::new(0., 2. * HALF + 5.), Vec2::new(0., -200.));
+        harness.step(60);
+
+        let joints = harness.joints();
+        assert_eq!(joints.len(), 1, "Boxes weren't glued at scale {scale}");
+        let (a1, a2) = harness.anchors()[0];
+        if joints[0].0 == bottom {
+            (a1, a2)
+        } else {
+            (a2, a1)
+        }
+    }
+
+    #[test]
+    fn glue_anchors_land_on_contact_surface() {
+        for scale in [1., 10., 50., 100., 250.] {
+            let (bottom, top) = glue_anchors(scale);
+            let tolerance = 1.;
+            assert!(
+                (bottom.y - HALF).abs() < tolerance,
+                "Anchor {bottom} isn't on top of the bottom box at scale {scale}"
+            );
+            assert!(
+                (top.y + HALF).abs() < tolerance,
+                "Anchor {top} isn't under the top box at scale {scale}"
+            );
+            assert!(bottom.x.abs() <= HALF + tolerance && top.x.abs() <= HALF + tolerance);
+        }
+    }
 }
@@ -0,0 +1,120 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use super::{physics::CollisionLayer, GameClock, OnGame, TilingMaterial};
+
+/// Kinematic obstacle of a [`Level`](super::level::Level)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlatformDefinition {
+    /// Half extents
+    pub size: (f32, f32),
+    pub position: (f32, f32),
+    #[serde(default)]
+    pub path: PlatformPath,
+    /// Radians per second, counterclockwise when positive
+    #[serde(default)]
+    pub spin: f32,
+}
+
+/// How the platform moves from where it starts
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum PlatformPath {
+    #[default]
+    Still,
+    /// Moves by the offset over the seconds and stays there
+    Linear { offset: (f32, f32), duration: f32 },
+    /// Moves by the offset and back, taking the seconds each way
+    PingPong { offset: (f32, f32), duration: f32 },
+    /// Circles around the start, taking the seconds for one round
+    Circular { radius: f32, period: f32 },
+}
+
+impl PlatformPath {
+    /// Whether the path takes a positive time, so that [`offset`](Self::offset) is finite
+    pub fn is_valid(self) -> bool {
+        match self {
+            PlatformPath::Still => true,
+            PlatformPath::Linear { duration, .. } | PlatformPath::PingPong { duration, .. } => {
+                duration > 0.
+            }
+            PlatformPath::Circular { period, .. } => period > 0.,
+        }
+    }
+
+    /// Offset from the start after the seconds
+    pub fn offset(self, time: f32) -> Vec2 {
+        match self {
+            PlatformPath::Still => Vec2::ZERO,
+            PlatformPath::Linear { offset, duration } => {
+                Vec2::new(offset.0, offset.1) * (time / duration).min(1.)
+            }
+            PlatformPath::PingPong { offset, duration } => {
+                let phase = (time / duration) % 2.;
+                Vec2::new(offset.0, offset.1) * (1. - (phase - 1.).abs())
+            }
+            PlatformPath::Circular { radius, period } => {
+                Vec2::from_angle(TAU * time / period) * radius
+            }
+        }
+    }
+}
+
+/// Kinematic body that follows its path. Items glue to it without it joining their cluster.
+#[derive(Component, Debug, Clone)]
+pub struct Platform {
+    pub start: Vec2,
+    pub path: PlatformPath,
+    pub spin: f32,
+}
+
+pub fn spawn_platforms(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<TilingMaterial>,
+    asset_server: &AssetServer,
+    platforms: &[PlatformDefinition],
+) {
+    let texture_handle = asset_server.load("bricks.png");
+    for definition in platforms {
+        let (hx, hy) = definition.size;
+        let start = Vec2::new(definition.position.0, definition.position.1);
+        let position = start + definition.path.offset(0.);
+        let transform = Transform::from_xyz(position.x, position.y, 0.);
+        commands.spawn((
+            Platform {
+                start,
+                path: definition.path,
+                spin: definition.spin,
+            },
+            RigidBody::KinematicPositionBased,
+            Collider::cuboid(hx, hy),
+            CollisionLayer::World,
+            CollisionLayer::World.groups(),
+            MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(Mesh::from(shape::Quad::new(2. * Vec2::new(hx, hy))))
+                    .into(),
+                material: materials.add(TilingMaterial::new(
+                    texture_handle.clone(),
+                    [hx, hy, 0., 0.],
+                )),
+                transform,
+                ..default()
+            },
+            OnGame,
+        ));
+    }
+}
+
+/// Moves platforms along their paths with the game clock, so they move the same in replays
+pub fn handle_platforms(clock: Res<GameClock>, mut platforms: Query<(&Platform, &mut Transform)>) {
+    let time = clock.0.elapsed_secs();
+    for (platform, mut transform) in platforms.iter_mut() {
+        let position = platform.start + platform.path.offset(time);
+        transform.translation = position.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(platform.spin * time);
+    }
+}
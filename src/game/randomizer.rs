@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::SmallRng,
+    seq::SliceRandom,
+    SeedableRng,
+};
+use serde::Deserialize;
+
+/// Decides which option comes next
+pub trait Randomizer: Send + Sync {
+    /// Picks index of the option. Options with zero weight are never picked.
+    fn pick(&mut self, weights: &[f32]) -> Option<usize>;
+}
+
+/// How spawn table picks its items.
+///
+/// Randomizer is chosen per table, as the game has no modes to choose it by.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum RandomizerKind {
+    /// Every pick is independent
+    #[default]
+    Uniform,
+    /// Every option is put in a bag as many times as its weight and the bag is emptied before refilling it
+    Bag,
+    /// Options found in the last `history` picks are rerolled up to `rerolls` times
+    History { history: usize, rerolls: usize },
+}
+
+impl RandomizerKind {
+    pub fn build(self, seed: u64) -> Box<dyn Randomizer> {
+        let rng = SmallRng::seed_from_u64(seed);
+        match self {
+            RandomizerKind::Uniform => Box::new(UniformRandomizer { rng }),
+            RandomizerKind::Bag => Box::new(BagRandomizer {
+                rng,
+                weights: vec![],
+                bag: vec![],
+            }),
+            RandomizerKind::History { history, rerolls } => Box::new(HistoryRandomizer {
+                rng,
+                history: VecDeque::with_capacity(history + 1),
+                size: history,
+                rerolls,
+            }),
+        }
+    }
+}
+
+pub struct UniformRandomizer {
+    rng: SmallRng,
+}
+
+impl Randomizer for UniformRandomizer {
+    fn pick(&mut self, weights: &[f32]) -> Option<usize> {
+        let distribution = WeightedIndex::new(weights).ok()?;
+        Some(distribution.sample(&mut self.rng))
+    }
+}
+
+pub struct BagRandomizer {
+    rng: SmallRng,
+    /// Weights the bag was filled with
+    weights: Vec<f32>,
+    bag: Vec<usize>,
+}
+
+impl Randomizer for BagRandomizer {
+    fn pick(&mut self, weights: &[f32]) -> Option<usize> {
+        // Options changed so the old bag isn't fair anymore
+        if self.weights != weights {
+            self.weights = weights.to_vec();
+            self.bag.clear();
+        }
+        if self.bag.is_empty() {
+            for (i, &weight) in weights.iter().enumerate() {
+                let copies = if weight > 0. {
+                    weight.round().max(1.) as usize
+                } else {
+                    0
+                };
+                self.bag.extend(std::iter::repeat_n(i, copies));
+            }
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop()
+    }
+}
+
+pub struct HistoryRandomizer {
+    rng: SmallRng,
+    history: VecDeque<usize>,
+    size: usize,
+    rerolls: usize,
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn pick(&mut self, weights: &[f32]) -> Option<usize> {
+        let distribution = WeightedIndex::new(weights).ok()?;
+        let mut pick = distribution.sample(&mut self.rng);
+        for _ in 0..self.rerolls {
+            if !self.history.contains(&pick) {
+                break;
+            }
+            pick = distribution.sample(&mut self.rng);
+        }
+        self.history.push_back(pick);
+        if self.history.len() > self.size {
+            self.history.pop_front();
+        }
+        Some(pick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picks(kind: RandomizerKind, seed: u64, weights: &[f32], n: usize) -> Vec<usize> {
+        let mut randomizer = kind.build(seed);
+        (0..n).map(|_| randomizer.pick(weights).unwrap()).collect()
+    }
+
+    fn counts(picks: &[usize], options: usize) -> Vec<usize> {
+        let mut counts = vec![0; options];
+        for &p in picks {
+            counts[p] += 1;
+        }
+        counts
+    }
+
+    const KINDS: [RandomizerKind; 3] = [
+        RandomizerKind::Uniform,
+        RandomizerKind::Bag,
+        RandomizerKind::History {
+            history: 2,
+            rerolls: 4,
+        },
+    ];
+
+    #[test]
+    fn same_seed_gives_same_picks() {
+        for kind in KINDS {
+            let weights = [1., 2., 1., 3.];
+            assert_eq!(
+                picks(kind, 42, &weights, 100),
+                picks(kind, 42, &weights, 100)
+            );
+        }
+    }
+
+    #[test]
+    fn zero_weights_are_never_picked() {
+        for kind in KINDS {
+            let weights = [1., 0., 2., 0.];
+            let counts = counts(&picks(kind, 7, &weights, 1000), weights.len());
+            assert_eq!(counts[1], 0);
+            assert_eq!(counts[3], 0);
+        }
+    }
+
+    #[test]
+    fn nothing_to_pick() {
+        for kind in KINDS {
+            assert_eq!(kind.build(0).pick(&[0., 0.]), None);
+            assert_eq!(kind.build(0).pick(&[]), None);
+        }
+    }
+
+    #[test]
+    fn uniform_follows_weights() {
+        let weights = [1., 2., 1.];
+        let counts = counts(
+            &picks(RandomizerKind::Uniform, 3, &weights, 40_000),
+            weights.len(),
+        );
+        for (count, expected) in counts.into_iter().zip([10_000, 20_000, 10_000]) {
+            assert!(count.abs_diff(expected) < 1_000, "{count} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn every_bag_has_each_option_weight_times() {
+        let weights = [1., 2., 1., 3.];
+        let bag_size = 7;
+        let picks = picks(RandomizerKind::Bag, 11, &weights, 100 * bag_size);
+        for bag in picks.chunks(bag_size) {
+            assert_eq!(counts(bag, weights.len()), vec![1, 2, 1, 3]);
+        }
+    }
+
+    #[test]
+    fn bag_limits_droughts_and_floods() {
+        let weights = [1.; 4];
+        let picks = picks(RandomizerKind::Bag, 5, &weights, 10_000);
+        for option in 0..weights.len() {
+            let positions = picks
+                .iter()
+                .enumerate()
+                .filter(|(_, &p)| p == option)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            // At worst option is first in one bag and last in the next one
+            assert!(positions.windows(2).all(|w| w[1] - w[0] <= 7));
+        }
+        // Same option can't come more than twice in a row
+        assert!(picks.windows(3).all(|w| w[0] != w[1] || w[1] != w[2]));
+    }
+
+    #[test]
+    fn bag_is_refilled_when_options_change() {
+        let mut randomizer = RandomizerKind::Bag.build(1);
+        randomizer.pick(&[1., 1., 0.]);
+        let picks = (0..3)
+            .map(|_| randomizer.pick(&[1., 1., 1.]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(counts(&picks, 3), vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn history_reduces_repeats() {
+        let weights = [1.; 4];
+        let repeats = |kind| {
+            picks(kind, 9, &weights, 10_000)
+                .windows(2)
+                .filter(|w| w[0] == w[1])
+                .count()
+        };
+        let uniform = repeats(RandomizerKind::Uniform);
+        let history = repeats(RandomizerKind::History {
+            history: 2,
+            rerolls: 4,
+        });
+        // Uniform repeats a quarter of the time
+        assert!(uniform > 2_000);
+        // Repeat needs the first roll and every reroll to hit the history
+        assert!(history < 300, "{history}");
+    }
+}
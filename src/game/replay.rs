@@ -0,0 +1,201 @@
+use std::{error::Error, fs};
+
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    determinism::Determinism,
+    throw::{get_window, Player},
+};
+use crate::{
+    utils::{arg_value, screen_to_world},
+    MainCamera,
+};
+
+/// Inputs of one game in the deterministic mode. Saved as RON files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    /// Score the game ended with
+    pub score: usize,
+    /// Inputs of every fixed tick from the start of the game
+    pub ticks: Vec<ThrowInput>,
+}
+
+impl Replay {
+    fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        // Keeps each tick on its own line
+        let config = PrettyConfig::default().depth_limit(2);
+        fs::write(path, ron::ser::to_string_pretty(self, config)?)?;
+        Ok(())
+    }
+}
+
+/// Mouse input of the player for the current tick, which throwing reads instead of the mouse
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThrowInput {
+    pub pressed: bool,
+    pub just_pressed: bool,
+    pub just_released: bool,
+    /// Cursor in world coordinates
+    pub target: (f32, f32),
+}
+
+impl ThrowInput {
+    pub fn target(&self) -> Vec2 {
+        Vec2::new(self.target.0, self.target.1)
+    }
+}
+
+/// Games are recorded with `--record <path>` and played back with `--replay <path>`.
+///
+/// Both turn on the deterministic mode. Recording uses the same seed for every game and overwrites
+/// the file when a game ends.
+#[derive(Resource, Debug, Default)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    Record {
+        path: String,
+        replay: Replay,
+    },
+    Playback {
+        replay: Replay,
+        tick: usize,
+        checked: bool,
+    },
+}
+
+impl ReplayMode {
+    pub fn from_args() -> Self {
+        if let Some(path) = arg_value("--replay") {
+            let replay = Replay::load(&path)
+                .unwrap_or_else(|err| panic!("Couldn't load replay {path}: {err}"));
+            return ReplayMode::Playback {
+                replay,
+                tick: 0,
+                checked: false,
+            };
+        }
+        if let Some(path) = arg_value("--record") {
+            return ReplayMode::Record {
+                path,
+                replay: Replay::default(),
+            };
+        }
+        ReplayMode::Off
+    }
+
+    /// Playback uses the seed of the replay, and recording needs a seed even if one wasn't given
+    pub fn determinism(&self, determinism: Determinism) -> Determinism {
+        match self {
+            ReplayMode::Off => determinism,
+            ReplayMode::Record { .. } => Determinism {
+                seed: Some(determinism.game_seed()),
+            },
+            ReplayMode::Playback { replay, .. } => Determinism {
+                seed: Some(replay.seed),
+            },
+        }
+    }
+}
+
+pub fn setup_replay(mut mode: ResMut<ReplayMode>, determinism: Res<Determinism>) {
+    match &mut *mode {
+        ReplayMode::Off => {}
+        ReplayMode::Record { replay, .. } => {
+            *replay = Replay {
+                seed: determinism.game_seed(),
+                ..default()
+            };
+        }
+        ReplayMode::Playback { tick, checked, .. } => {
+            *tick = 0;
+            *checked = false;
+        }
+    }
+}
+
+pub fn handle_throw_input(
+    mut input: ResMut<ThrowInput>,
+    mut mode: ResMut<ReplayMode>,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    players: Query<&Player>,
+) {
+    if let ReplayMode::Playback {
+        replay,
+        tick,
+        checked,
+    } = &mut *mode
+    {
+        if let Some(&recorded) = replay.ticks.get(*tick) {
+            *input = recorded;
+            *tick += 1;
+        } else {
+            *input = ThrowInput::default();
+            if !*checked {
+                *checked = true;
+                check_score(replay, players.single().score);
+            }
+        }
+        return;
+    }
+
+    let (camera, camera_transform) = cameras.single();
+    let window = get_window(camera, &windows);
+    let target = window
+        .cursor_position()
+        .map(|screen_pos| screen_to_world(window, camera, camera_transform, screen_pos))
+        .unwrap_or(Vec2::ZERO);
+    *input = ThrowInput {
+        pressed: buttons.pressed(MouseButton::Left),
+        just_pressed: buttons.just_pressed(MouseButton::Left),
+        just_released: buttons.just_released(MouseButton::Left),
+        target: (target.x, target.y),
+    };
+
+    if let ReplayMode::Record { replay, .. } = &mut *mode {
+        replay.ticks.push(*input);
+    }
+}
+
+/// Saves the recording, or checks the playback if the game ended before the inputs ran out
+pub fn handle_replay_end(mut mode: ResMut<ReplayMode>, players: Query<&Player>) {
+    let score = players.single().score;
+    match &mut *mode {
+        ReplayMode::Off => {}
+        ReplayMode::Record { path, replay } => {
+            replay.score = score;
+            match replay.save(path) {
+                Ok(()) => info!("Saved replay of {} ticks to {path}", replay.ticks.len()),
+                Err(err) => error!("Couldn't save replay to {path}: {err}"),
+            }
+        }
+        ReplayMode::Playback {
+            replay, checked, ..
+        } => {
+            if !*checked {
+                *checked = true;
+                check_score(replay, score);
+            }
+        }
+    }
+}
+
+fn check_score(replay: &Replay, score: usize) {
+    if score == replay.score {
+        info!("Replay finished with the recorded score {score}");
+    } else {
+        error!(
+            "Replay diverged: finished with score {score}, but {} was recorded",
+            replay.score
+        );
+    }
+}
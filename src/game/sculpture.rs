@@ -0,0 +1,448 @@
+use std::{
+    error::Error,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    math::Vec3Swizzles,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use bevy_rapier2d::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cluster::{item_bounds, ClusterId, StuckItems},
+    fracture::{spawn_fragment, Fragment},
+    items::{spawn_item, Item, ItemAssets, ItemDefinition},
+    physics::{body_of, CollisionLayer, Glue},
+    throw::get_window,
+    DeathTimer, OnGame, StickyMaterial,
+};
+use crate::{
+    utils::{arg_value, screen_to_world},
+    GameState, MainCamera,
+};
+
+/// Where exported sculptures are saved, relative to the assets
+pub const SCULPTURE_DIR: &str = "sculptures";
+const EXPORT_KEY: KeyCode = KeyCode::F5;
+const GALLERY_KEY: KeyCode = KeyCode::F6;
+
+/// Cluster of imported sculptures.
+///
+/// Ids from [`StuckItems`] start from one, so this never clashes with them.
+pub const SCULPTURE_CLUSTER: ClusterId = ClusterId(0);
+
+/// Glued structure saved by a player. Loaded from `.sculpture.ron` files.
+///
+/// Positions are relative to the bottom centre of the sculpture.
+#[derive(Debug, Serialize, Deserialize, TypeUuid)]
+#[uuid = "c2e85f17-4d6a-4b90-a3f1-7e0d9b5c2a68"]
+pub struct Sculpture {
+    pub items: Vec<SculptureItem>,
+    pub joints: Vec<SculptureJoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SculptureItem {
+    /// Name of the item definition
+    pub item: String,
+    pub radius: f32,
+    pub position: (f32, f32),
+    pub rotation: f32,
+    /// Set when the item is a piece of a broken item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<SculptureFragment>,
+}
+
+/// Piece of a broken item
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SculptureFragment {
+    /// Convex outline in the coordinates of the whole item
+    pub polygon: Vec<(f32, f32)>,
+    /// Index of the collider definition the piece came from
+    pub collider: usize,
+}
+
+/// Glue between two items of the sculpture
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SculptureJoint {
+    /// Indices of the glued items. The joint is owned by the second one.
+    pub items: (usize, usize),
+    /// Anchors in the local coordinates of the items
+    pub anchors: ((f32, f32), (f32, f32)),
+    /// Rotations of the joint frames relative to the items
+    pub bases: (f32, f32),
+}
+
+#[derive(Default)]
+pub struct SculptureLoader;
+
+impl AssetLoader for SculptureLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let sculpture = ron::de::from_bytes::<Sculpture>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(sculpture));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sculpture.ron"]
+    }
+}
+
+/// Sculpture shown in the gallery
+#[derive(Resource, Default)]
+pub struct Gallery {
+    /// Asset path of the sculpture
+    pub path: Option<String>,
+    /// Are the items of the sculpture fixed in place
+    pub frozen: bool,
+    /// Gallery is shown instead of the game when it starts
+    pub on_start: bool,
+}
+
+impl Gallery {
+    /// Sculpture can be given with `--gallery <path>`
+    pub fn from_args() -> Self {
+        let path = arg_value("--gallery");
+        Self {
+            on_start: path.is_some(),
+            path,
+            frozen: false,
+        }
+    }
+}
+
+/// Saves the cluster under the cursor as a sculpture, which the gallery then shows
+pub fn handle_sculpture_export(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    rapier_context: Res<RapierContext>,
+    definitions: Res<Assets<ItemDefinition>>,
+    stuck_items: Query<&StuckItems>,
+    parents: Query<&Parent>,
+    items: Query<
+        (
+            Option<&Item>,
+            Option<&Fragment>,
+            &GlobalTransform,
+            Option<&Children>,
+        ),
+        Or<(With<Item>, With<Fragment>)>,
+    >,
+    impulse_joints: Query<(&ImpulseJoint, &Parent), With<Glue>>,
+    colliders: Query<&RapierColliderHandle>,
+    mut gallery: ResMut<Gallery>,
+) {
+    if !keys.just_pressed(EXPORT_KEY) {
+        return;
+    }
+    let (camera, camera_transform) = cameras.single();
+    let window = get_window(camera, &windows);
+    let cursor = match window.cursor_position() {
+        Some(pos) => screen_to_world(window, camera, camera_transform, pos),
+        None => return,
+    };
+
+    let mut hovered = None;
+    rapier_context.intersections_with_point(cursor, QueryFilter::default(), |collider| {
+        let entity = body_of(collider, parents.get(collider).ok());
+        if items.contains(entity) {
+            hovered = Some(entity);
+        }
+        hovered.is_none()
+    });
+    let hovered = match hovered {
+        Some(hovered) => hovered,
+        None => return,
+    };
+
+    let stuck_items = stuck_items.single();
+    let members = match stuck_items.cluster(hovered) {
+        Some(id) => stuck_items.members(id).collect(),
+        None => vec![hovered],
+    };
+
+    let mut bounds: Option<(Vec2, Vec2)> = None;
+    for &e in &members {
+        if let Ok((.., children)) = items.get(e) {
+            if let Some((min, max)) = item_bounds(e, children, &rapier_context, &colliders) {
+                bounds = Some(bounds.map_or((min, max), |(a, b)| (a.min(min), b.max(max))));
+            }
+        }
+    }
+    let origin = bounds.map_or(Vec2::ZERO, |(min, max)| {
+        Vec2::new(0.5 * (min.x + max.x), min.y)
+    });
+
+    let mut sculpture = Sculpture {
+        items: vec![],
+        joints: vec![],
+    };
+    let mut indices = HashMap::new();
+    for &e in &members {
+        if let Ok((item, fragment, transform, _)) = items.get(e) {
+            let (handle, radius) = match (item, fragment) {
+                (Some(item), _) => (&item.definition, item.radius),
+                (None, Some(fragment)) => (&fragment.definition, fragment.radius),
+                (None, None) => continue,
+            };
+            if let Some(definition) = definitions.get(handle) {
+                indices.insert(e, sculpture.items.len());
+                sculpture.items.push(sculpture_item(
+                    definition.name.clone(),
+                    radius,
+                    fragment,
+                    transform,
+                    origin,
+                ));
+            }
+        }
+    }
+    for (joint, parent) in impulse_joints.iter() {
+        if let (Some(&i1), Some(&i2)) = (indices.get(&joint.parent), indices.get(&parent.get())) {
+            let (a1, a2) = (joint.data.local_anchor1(), joint.data.local_anchor2());
+            sculpture.joints.push(SculptureJoint {
+                items: (i1, i2),
+                anchors: ((a1.x, a1.y), (a2.x, a2.y)),
+                bases: (joint.data.local_basis1(), joint.data.local_basis2()),
+            });
+        }
+    }
+
+    let name = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    match save_sculpture(&sculpture, &format!("{name}.sculpture.ron")) {
+        Ok(path) => {
+            info!("Saved sculpture to {path}");
+            gallery.path = Some(path);
+            spawn_saved(&mut commands, &asset_server, cursor);
+        }
+        Err(err) => error!("Couldn't save sculpture: {err}"),
+    }
+}
+
+fn sculpture_item(
+    item: String,
+    radius: f32,
+    fragment: Option<&Fragment>,
+    transform: &GlobalTransform,
+    origin: Vec2,
+) -> SculptureItem {
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    let position = translation.xy() - origin;
+    SculptureItem {
+        item,
+        radius,
+        position: (position.x, position.y),
+        rotation: rotation.to_euler(EulerRot::XYZ).2,
+        fragment: fragment.map(|fragment| SculptureFragment {
+            polygon: fragment.polygon.iter().map(|p| (p.x, p.y)).collect(),
+            collider: fragment.collider,
+        }),
+    }
+}
+
+/// Writes the sculpture to the assets and returns its asset path
+fn save_sculpture(sculpture: &Sculpture, file_name: &str) -> Result<String, Box<dyn Error>> {
+    let dir = Path::new("assets").join(SCULPTURE_DIR);
+    fs::create_dir_all(&dir)?;
+    let ron = ron::ser::to_string_pretty(sculpture, PrettyConfig::default())?;
+    fs::write(dir.join(file_name), ron)?;
+    Ok(format!("{SCULPTURE_DIR}/{file_name}"))
+}
+
+fn spawn_saved(commands: &mut Commands, asset_server: &AssetServer, pos: Vec2) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "saved!",
+                TextStyle {
+                    font: asset_server.load("fonts/MajorMonoDisplay-Regular.ttf"),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_alignment(TextAlignment::CENTER),
+            transform: Transform::from_xyz(pos.x, pos.y, 10.),
+            ..default()
+        },
+        DeathTimer(Timer::from_seconds(0.5, TimerMode::Once)),
+        OnGame,
+    ));
+}
+
+/// Opens the gallery with the last saved sculpture
+pub fn handle_gallery_opening(
+    keys: Res<Input<KeyCode>>,
+    gallery: Res<Gallery>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if keys.just_pressed(GALLERY_KEY) && gallery.path.is_some() {
+        game_state.set(GameState::Gallery).unwrap();
+    }
+}
+
+/// Spawns the items of the sculpture glued together with its bottom centre at the position.
+///
+/// Items whose definitions aren't in the catalog anymore are left out.
+pub fn spawn_sculpture(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    custom_materials: &mut ResMut<Assets<StickyMaterial>>,
+    items: &ItemAssets,
+    sculpture: &Sculpture,
+    position: Vec2,
+    frozen: bool,
+) -> Vec<Entity> {
+    let spawned = sculpture
+        .items
+        .iter()
+        .map(|item| {
+            let handle = items.by_name(&item.item);
+            if handle.is_none() {
+                warn!("Sculpture has unknown item {}", item.item);
+            }
+            handle.map(|handle| {
+                let pos = position + Vec2::new(item.position.0, item.position.1);
+                let mut cmds = match &item.fragment {
+                    Some(fragment) => spawn_fragment(
+                        commands,
+                        asset_server,
+                        meshes,
+                        custom_materials,
+                        items,
+                        handle,
+                        item.radius,
+                        fragment
+                            .polygon
+                            .iter()
+                            .map(|&(x, y)| Vec2::new(x, y))
+                            .collect(),
+                        fragment.collider,
+                    ),
+                    None => spawn_item(
+                        commands,
+                        asset_server,
+                        meshes,
+                        custom_materials,
+                        items,
+                        handle,
+                        item.radius,
+                        CollisionLayer::Item,
+                    ),
+                };
+                cmds.insert((
+                    TransformBundle::from(
+                        Transform::from_xyz(pos.x, pos.y, 0.)
+                            .with_rotation(Quat::from_rotation_z(item.rotation)),
+                    ),
+                    SCULPTURE_CLUSTER,
+                ));
+                if frozen {
+                    cmds.insert(RigidBody::Fixed);
+                }
+                cmds.id()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for joint in &sculpture.joints {
+        let (i1, i2) = joint.items;
+        if let (Some(&Some(e1)), Some(&Some(e2))) = (spawned.get(i1), spawned.get(i2)) {
+            let ((x1, y1), (x2, y2)) = joint.anchors;
+            let data = FixedJointBuilder::new()
+                .local_anchor1(Vec2::new(x1, y1))
+                .local_basis1(joint.bases.0)
+                .local_anchor2(Vec2::new(x2, y2))
+                .local_basis2(joint.bases.1);
+            commands.entity(e2).add_children(|builder| {
+                builder.spawn((ImpulseJoint::new(e1, data), Glue::default()));
+            });
+        }
+    }
+    spawned.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_survive_export_and_load() {
+        let origin = Vec2::new(100., 50.);
+        let fragment = Fragment {
+            definition: Handle::default(),
+            radius: 30.,
+            polygon: vec![Vec2::new(0., 0.), Vec2::new(20., -5.), Vec2::new(10., 15.)],
+            collider: 1,
+        };
+        let transform =
+            Transform::from_xyz(110., 60., 0.).with_rotation(Quat::from_rotation_z(0.5));
+        let sculpture = Sculpture {
+            items: vec![
+                sculpture_item(
+                    "plate".into(),
+                    30.,
+                    None,
+                    &GlobalTransform::from_xyz(90., 50., 0.),
+                    origin,
+                ),
+                sculpture_item(
+                    "plate".into(),
+                    30.,
+                    Some(&fragment),
+                    &transform.into(),
+                    origin,
+                ),
+            ],
+            joints: vec![SculptureJoint {
+                items: (0, 1),
+                anchors: ((5., 0.), (-5., 0.)),
+                bases: (0., 0.),
+            }],
+        };
+
+        let ron = ron::ser::to_string_pretty(&sculpture, PrettyConfig::default()).unwrap();
+        let loaded = ron::de::from_str::<Sculpture>(&ron).unwrap();
+        assert_eq!(loaded.items.len(), 2);
+        assert_eq!(loaded.items[0].fragment, None);
+        let piece = &loaded.items[1];
+        assert_eq!(piece.position, (10., 10.));
+        assert!((piece.rotation - 0.5).abs() < 1e-5);
+        assert_eq!(
+            piece.fragment,
+            Some(SculptureFragment {
+                polygon: vec![(0., 0.), (20., -5.), (10., 15.)],
+                collider: 1,
+            })
+        );
+        assert_eq!(loaded.joints[0].items, (0, 1));
+    }
+
+    #[test]
+    fn whole_items_load_without_fragment() {
+        let sculpture = ron::de::from_str::<Sculpture>(
+            "(items: [(item: \"shoe\", radius: 50., position: (0., 0.), rotation: 0.)], joints: [])",
+        )
+        .unwrap();
+        assert_eq!(sculpture.items[0].fragment, None);
+    }
+}
@@ -5,21 +5,23 @@ use bevy::{
     sprite::Material2d,
 };
 
-use super::throw::Throwable;
+use super::{stickiness::Stickiness, throw::Throwable};
 
-#[derive(Component)]
-struct StickyEffect;
 pub fn handle_stickiness_effect(
-    throwables: Query<(&Throwable, &Handle<StickyMaterial>)>,
+    throwables: Query<(&Throwable, Option<&Stickiness>, &Handle<StickyMaterial>)>,
     mut custom_materials: ResMut<Assets<StickyMaterial>>,
 ) {
-    for (throwable, material) in throwables.iter() {
+    for (throwable, stickiness, material) in throwables.iter() {
         if let Some(material) = custom_materials.get_mut(material) {
             if throwable.sticky {
                 material.sticky = 1;
             } else {
                 material.sticky = 0;
             }
+            if let Some(stickiness) = stickiness {
+                material.policy = stickiness.policy.shader_index();
+                material.remaining = stickiness.remaining();
+            }
         }
     }
 }
@@ -40,6 +42,12 @@ pub struct StickyMaterial {
     #[texture(2)]
     #[sampler(3)]
     pub color_texture: Handle<Image>,
+    /// [`StickinessPolicy::shader_index`](super::stickiness::StickinessPolicy::shader_index)
+    #[uniform(4)]
+    pub policy: i32,
+    /// Fraction of the sticky time left for timed stickiness
+    #[uniform(5)]
+    pub remaining: f32,
 }
 
 impl Material2d for TilingMaterial {
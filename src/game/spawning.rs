@@ -0,0 +1,119 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use super::randomizer::{Randomizer, RandomizerKind};
+
+pub const QUEUE_TABLE: &str = "items/queue.spawns.ron";
+pub const DROPS_TABLE: &str = "items/drops.spawns.ron";
+
+/// Weighted list of items to spawn. Loaded from `.spawns.ron` files.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "6e0b2d94-18c7-4a3f-b5d2-c93f7e1a0b86"]
+pub struct SpawnTable {
+    pub entries: Vec<SpawnEntry>,
+    #[serde(default)]
+    pub randomizer: RandomizerKind,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpawnEntry {
+    /// Name of the item in the catalog
+    pub item: String,
+    pub weight: f32,
+    /// Range the radius is picked from. Defaults to the radius of the item definition.
+    #[serde(default)]
+    pub size: Option<(f32, f32)>,
+    #[serde(default)]
+    pub min_score: usize,
+    /// Seconds since the game started
+    #[serde(default)]
+    pub min_time: f32,
+}
+
+/// State of the game the spawn conditions are checked against
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpawnContext {
+    pub score: usize,
+    pub elapsed: f32,
+}
+
+impl SpawnEntry {
+    pub fn allowed(&self, context: &SpawnContext) -> bool {
+        context.score >= self.min_score && context.elapsed >= self.min_time
+    }
+
+    pub fn radius<R: Rng>(&self, rng: &mut R) -> Option<f32> {
+        self.size.map(|(min, max)| {
+            if min < max {
+                rng.gen_range(min..=max)
+            } else {
+                min
+            }
+        })
+    }
+}
+
+impl SpawnTable {
+    pub fn choose(
+        &self,
+        randomizer: &mut dyn Randomizer,
+        context: &SpawnContext,
+    ) -> Option<&SpawnEntry> {
+        // Entries that are not allowed keep their place so the randomizer sees stable indices
+        let weights = self
+            .entries
+            .iter()
+            .map(|entry| {
+                if entry.allowed(context) {
+                    entry.weight
+                } else {
+                    0.
+                }
+            })
+            .collect::<Vec<_>>();
+        randomizer.pick(&weights).map(|i| &self.entries[i])
+    }
+}
+
+#[derive(Default)]
+pub struct SpawnTableLoader;
+
+impl AssetLoader for SpawnTableLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let table = ron::de::from_bytes::<SpawnTable>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(table));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["spawns.ron"]
+    }
+}
+
+/// Tables for the items player gets to throw and for the items dropping from the sky.
+///
+/// Game has a single mode, so these are the only tables. Another mode would load its own pair.
+#[derive(Resource)]
+pub struct SpawnTables {
+    pub queue: Handle<SpawnTable>,
+    pub drops: Handle<SpawnTable>,
+}
+
+pub fn load_spawn_tables(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SpawnTables {
+        queue: asset_server.load(QUEUE_TABLE),
+        drops: asset_server.load(DROPS_TABLE),
+    });
+}
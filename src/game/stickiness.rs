@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use bevy_rapier2d::{prelude::*, rapier::prelude::CollisionEventFlags};
+use serde::Deserialize;
+
+use super::physics::body_of;
+use super::throw::Throwable;
+
+/// How an item sticks to other items
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum StickinessPolicy {
+    /// Glues to everything it touches
+    Always,
+    /// Slides off everything and can't be glued
+    Never,
+    /// Becomes sticky when glued to a sticky item
+    #[default]
+    Contagious,
+    /// Sticky for this many seconds after landing
+    Timed(f32),
+}
+
+impl StickinessPolicy {
+    /// Which policy the sticky shader shows
+    pub fn shader_index(&self) -> i32 {
+        match self {
+            StickinessPolicy::Always => 0,
+            StickinessPolicy::Never => 1,
+            StickinessPolicy::Contagious => 2,
+            StickinessPolicy::Timed(_) => 3,
+        }
+    }
+
+    /// Can the item be glued to anything
+    pub fn glues(&self) -> bool {
+        *self != StickinessPolicy::Never
+    }
+
+    /// Is the item sticky when it drops from the sky
+    pub fn starts_sticky(&self) -> bool {
+        match self {
+            StickinessPolicy::Always | StickinessPolicy::Contagious => true,
+            // Timed items wait until they land
+            StickinessPolicy::Never | StickinessPolicy::Timed(_) => false,
+        }
+    }
+
+    /// Does the item become sticky when it gets glued
+    pub fn spreads(&self) -> bool {
+        *self == StickinessPolicy::Contagious
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Stickiness {
+    pub policy: StickinessPolicy,
+    /// Started when item with timed stickiness lands
+    pub timer: Option<Timer>,
+}
+
+impl Stickiness {
+    pub fn new(policy: StickinessPolicy) -> Self {
+        Self {
+            policy,
+            timer: None,
+        }
+    }
+
+    /// Fraction of the sticky time left
+    pub fn remaining(&self) -> f32 {
+        self.timer.as_ref().map_or(1., |timer| 1. - timer.percent())
+    }
+}
+
+/// Keeps the stickiness of throwables in line with their policies
+pub fn handle_stickiness(
+    time: Res<Time>,
+    mut collision_events: EventReader<CollisionEvent>,
+    parents: Query<&Parent>,
+    mut items: Query<(&mut Throwable, &mut Stickiness)>,
+) {
+    for collision_event in collision_events.iter() {
+        if let CollisionEvent::Started(a, b, flags) = collision_event {
+            if flags.contains(CollisionEventFlags::SENSOR) {
+                continue;
+            }
+            for collider in [*a, *b] {
+                let entity = body_of(collider, parents.get(collider).ok());
+                if let Ok((mut throwable, mut stickiness)) = items.get_mut(entity) {
+                    if let StickinessPolicy::Timed(seconds) = stickiness.policy {
+                        if stickiness.timer.is_none() {
+                            stickiness.timer = Some(Timer::from_seconds(seconds, TimerMode::Once));
+                            throwable.sticky = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for (mut throwable, mut stickiness) in items.iter_mut() {
+        match stickiness.policy {
+            StickinessPolicy::Always => throwable.sticky = true,
+            StickinessPolicy::Never => throwable.sticky = false,
+            StickinessPolicy::Contagious => {}
+            StickinessPolicy::Timed(_) => match &mut stickiness.timer {
+                Some(timer) => {
+                    if timer.tick(time.delta()).finished() {
+                        throwable.sticky = false;
+                    }
+                }
+                // Not landed yet
+                None => throwable.sticky = false,
+            },
+        }
+    }
+}
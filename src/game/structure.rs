@@ -0,0 +1,122 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    cluster::{item_bounds, Cluster, StuckItems},
+    fracture::Fragment,
+    items::Item,
+    physics::visualise_scoring,
+    throw::{Player, Throwable},
+};
+
+/// Points for every 100 pixels of height
+const HEIGHT_POINTS: f32 = 10.;
+/// Points for every 100 pixels reaching out past the bottom item
+const OVERHANG_POINTS: f32 = 20.;
+/// Points for a perfectly balanced structure
+const BALANCE_POINTS: f32 = 30.;
+/// Points for every different kind of item after the first one
+const VARIETY_POINTS: usize = 15;
+/// Smaller structures are too easy to balance
+const BALANCE_MIN_MEMBERS: usize = 3;
+
+/// Structure bonuses already awarded for the cluster
+#[derive(Component, Default, Debug)]
+pub struct StructureScore {
+    /// Members when the cluster was last evaluated
+    pub members: usize,
+    pub height: usize,
+    pub overhang: usize,
+    pub balance: usize,
+    pub variety: usize,
+}
+
+/// Evaluates clusters whose members changed and awards only the improvements over earlier bonuses
+pub fn handle_structure_scoring(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    rapier_context: Res<RapierContext>,
+    mut clusters: Query<(&Cluster, Option<&mut StructureScore>, Entity)>,
+    stuck_items: Query<&StuckItems>,
+    items: Query<(
+        Option<&Item>,
+        Option<&Fragment>,
+        Option<&Throwable>,
+        Option<&Children>,
+    )>,
+    colliders: Query<&RapierColliderHandle>,
+    mut players: Query<&mut Player>,
+) {
+    let stuck_items = stuck_items.single();
+    for (cluster, score, entity) in clusters.iter_mut() {
+        let mut new_score = StructureScore::default();
+        let score = match score {
+            Some(score) => score.into_inner(),
+            None => &mut new_score,
+        };
+        // Cluster is gone and its entity is despawned at the end of the stage
+        if score.members == cluster.members || stuck_items.members(cluster.id).next().is_none() {
+            continue;
+        }
+        score.members = cluster.members;
+
+        let mut kinds = HashSet::new();
+        let mut base: Option<(Vec2, Vec2)> = None;
+        let mut player = None;
+        for e in stuck_items.members(cluster.id) {
+            if let Ok((item, fragment, throwable, children)) = items.get(e) {
+                if let Some(definition) = item
+                    .map(|i| &i.definition)
+                    .or_else(|| fragment.map(|f| &f.definition))
+                {
+                    kinds.insert(definition.clone());
+                }
+                player = player.or_else(|| throwable.and_then(|t| t.player));
+                if let Some(bounds) = item_bounds(e, children, &rapier_context, &colliders) {
+                    if base.is_none_or(|(min, _)| bounds.0.y < min.y) {
+                        base = Some(bounds);
+                    }
+                }
+            }
+        }
+
+        let mut bonuses = vec![];
+        let height = (cluster.size().y / 100. * HEIGHT_POINTS) as usize;
+        bonuses.push(("height", height.saturating_sub(score.height)));
+        score.height = score.height.max(height);
+
+        if let Some((base_min, base_max)) = base {
+            let reach = (base_min.x - cluster.min.x).max(cluster.max.x - base_max.x);
+            let overhang = (reach.max(0.) / 100. * OVERHANG_POINTS) as usize;
+            bonuses.push(("overhang", overhang.saturating_sub(score.overhang)));
+            score.overhang = score.overhang.max(overhang);
+
+            if cluster.members >= BALANCE_MIN_MEMBERS {
+                // Centre of mass right above the middle of the base is perfect balance
+                let half_width = (0.5 * (base_max.x - base_min.x)).max(1.);
+                let offset = (cluster.center_of_mass.x - 0.5 * (base_min.x + base_max.x)).abs();
+                let balance = ((1. - offset / half_width).max(0.) * BALANCE_POINTS) as usize;
+                bonuses.push(("balance", balance.saturating_sub(score.balance)));
+                score.balance = score.balance.max(balance);
+            }
+        }
+
+        let variety = kinds.len().saturating_sub(1) * VARIETY_POINTS;
+        bonuses.push(("variety", variety.saturating_sub(score.variety)));
+        score.variety = score.variety.max(variety);
+
+        if let Some(mut player) = player.and_then(|p| players.get_mut(p).ok()) {
+            let top = Vec2::new(cluster.center_of_mass.x, cluster.max.y);
+            let awarded = bonuses.into_iter().filter(|&(_, points)| points > 0);
+            for (i, (name, points)) in awarded.enumerate() {
+                let pos = top + Vec2::new(0., 40. * (i + 1) as f32);
+                visualise_scoring(&asset_server, pos, &mut commands, name, points, 1, points);
+                player.score += points;
+            }
+        }
+
+        if new_score.members > 0 {
+            commands.entity(entity).insert(new_score);
+        }
+    }
+}
@@ -1,21 +1,14 @@
-use std::time::Duration;
-
-use crate::{
-    game::{Current, DeathTimer, OnGame, SOURCE, STORAGE},
-    utils::{screen_to_world, EntityCommandsExt},
-    MainCamera,
-};
-use bevy::{
-    ecs::system::SystemParam,
-    math::Vec3Swizzles,
-    prelude::*,
-    render::{camera::RenderTarget, render_resource::Texture},
-    sprite::Material2d,
-    utils::HashSet,
-};
+use crate::game::{Current, GameClock, OnGame, SOURCE, STORAGE};
+use bevy::{math::Vec3Swizzles, prelude::*, render::camera::RenderTarget, utils::HashSet};
 use bevy_rapier2d::prelude::*;
 
-use super::{items::random_item, StickyMaterial};
+use super::{
+    items::{random_item, ItemAssets},
+    physics::CollisionLayer,
+    replay::ThrowInput,
+    spawning::SpawnContext,
+    StickyMaterial,
+};
 
 #[derive(Clone, Debug, Component)]
 pub struct Throwable {
@@ -47,64 +40,48 @@ pub struct Player {
     pub prev_mouse: Option<Vec2>,
 }
 
-#[derive(Resource)]
-pub struct ThrowIndicator {
-    pub timer: Timer,
+/// Impulse the current item is thrown with towards the target
+pub fn throw_impulse(player: &Player, target: Vec2) -> Vec2 {
+    let dir = (target - SOURCE).normalize_or_zero();
+    dir * 140. + dir * player.hold_timer.percent() * 300.
 }
 
-#[derive(Component)]
-pub struct Ghost(pub Entity);
-
 pub fn handle_throwing(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     time: Res<Time>,
-    buttons: Res<Input<MouseButton>>,
+    input: Res<ThrowInput>,
     mut current: ResMut<Current>,
-    mut indicator: ResMut<ThrowIndicator>,
-    windows: Res<Windows>,
-    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    (restitutions, collider_mass_props, colliders, transforms, global_transforms, velocities): (
-        Query<&Restitution>,
-        Query<&ColliderMassProperties>,
-        Query<&Collider>,
-        Query<&Transform>,
-        Query<&GlobalTransform>,
-        Query<&Velocity>,
-    ),
-    childrens: Query<&Children>,
+    velocities: Query<&Velocity>,
     mut players: Query<(&mut Player, &Transform, Entity)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut custom_materials: ResMut<Assets<StickyMaterial>>,
+    (items, clock): (ItemAssets, Res<GameClock>),
 ) {
-    let (camera, camera_transform) = cameras.single();
-
-    let window = get_window(camera, &windows);
-
-    let target = window
-        .cursor_position()
-        .map(|screen_pos| screen_to_world(window, camera, camera_transform, screen_pos))
-        .unwrap_or(Vec2::ZERO);
-
-    let dir = (target - SOURCE).normalize_or_zero();
+    let target = input.target();
 
     for (mut player, pos, player_entity) in players.iter_mut() {
         if player.cooldown_timer.tick(time.delta()).finished() {
-            if buttons.just_pressed(MouseButton::Left) && current.current.is_none() {
+            if input.just_pressed && current.current.is_none() {
                 generate_item(
                     &mut commands,
                     &asset_server,
                     &mut meshes,
                     &mut custom_materials,
+                    &items,
+                    &SpawnContext {
+                        score: player.score,
+                        elapsed: clock.0.elapsed_secs(),
+                    },
                     &mut current,
                 );
                 select_first_item(&mut commands, &mut current);
                 player.hold_timer.reset();
             }
 
-            let impulse = dir * 140. + dir * player.hold_timer.percent() * 300.;
+            let impulse = throw_impulse(&player, target);
 
-            if buttons.pressed(MouseButton::Left) {
+            if input.pressed {
                 if let Some(cur) = current.current {
                     player.hold_timer.tick(time.delta());
 
@@ -129,28 +106,10 @@ pub fn handle_throwing(
 
                         player.prev_mouse = Some(target);
                     }
-
-                    if indicator.timer.tick(time.delta()).just_finished() {
-                        let indicator_size = (0.1 + 0.9 * player.hold_timer.percent()).powf(2.);
-                        spawn_indicator(
-                            &mut commands,
-                            indicator_size,
-                            &asset_server,
-                            impulse,
-                            cur,
-                            dir,
-                            &childrens,
-                            &transforms,
-                            &global_transforms,
-                            &restitutions,
-                            &colliders,
-                            &collider_mass_props,
-                        );
-                    }
                 }
             }
 
-            if buttons.just_released(MouseButton::Left) && player.disables.is_empty() {
+            if input.just_released && player.disables.is_empty() {
                 if let Some(cur) = current.current.take() {
                     player.disables.insert(cur);
                     player.cooldown_timer.reset();
@@ -158,6 +117,7 @@ pub fn handle_throwing(
                     commands
                         .entity(cur)
                         .remove::<IgnoreCollisions>()
+                        .insert(CollisionLayer::Item)
                         .insert(GravityScale(1.))
                         .insert(Throwable::new(Some(player_entity), false))
                         .insert(LockedAxes::empty())
@@ -171,7 +131,7 @@ pub fn handle_throwing(
     }
 }
 
-fn get_window<'a>(camera: &'a Camera, windows: &'a Windows) -> &'a Window {
+pub fn get_window<'a>(camera: &'a Camera, windows: &'a Windows) -> &'a Window {
     if let RenderTarget::Window(id) = camera.target {
         windows.get(id)
     } else {
@@ -180,68 +140,6 @@ fn get_window<'a>(camera: &'a Camera, windows: &'a Windows) -> &'a Window {
     .unwrap()
 }
 
-fn spawn_indicator(
-    commands: &mut Commands,
-    indicator_size: f32,
-    asset_server: &AssetServer,
-    impulse: Vec2,
-    cur: Entity,
-    dir: Vec2,
-    childrens: &Query<&Children>,
-    transforms: &Query<&Transform>,
-    global_transforms: &Query<&GlobalTransform>,
-    restitutions: &Query<&Restitution>,
-    colliders: &Query<&Collider>,
-    collider_mass_props: &Query<&ColliderMassProperties>,
-) {
-    commands
-        .spawn((
-            SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(150., 150.) * indicator_size),
-                    ..default()
-                },
-                texture: asset_server.load("indicator.png"),
-                ..default()
-            },
-            RigidBody::Dynamic,
-            Ghost(cur),
-            ActiveEvents::COLLISION_EVENTS,
-            ActiveHooks::FILTER_CONTACT_PAIRS,
-            DeathTimer(Timer::new(Duration::from_secs_f32(0.5), TimerMode::Once)),
-            ExternalImpulse {
-                impulse,
-                torque_impulse: 0.,
-            },
-        ))
-        .with_children(|children| {
-            if let Ok(cur_children) = childrens.get(cur) {
-                for &child in cur_children {
-                    children
-                        .spawn((
-                            ActiveEvents::COLLISION_EVENTS,
-                            ActiveHooks::FILTER_CONTACT_PAIRS,
-                        ))
-                        .maybe_insert(transforms.get(child).ok().cloned())
-                        .maybe_insert(global_transforms.get(child).ok().cloned())
-                        .maybe_insert(restitutions.get(child).ok().cloned())
-                        .maybe_insert(colliders.get(child).ok().cloned())
-                        .maybe_insert(collider_mass_props.get(child).ok().cloned());
-                }
-            }
-        })
-        .maybe_insert(transforms.get(cur).ok().cloned().map(|t| Transform {
-            translation: Vec3::new(t.translation.x, t.translation.y, 10.)
-                + Vec3::new(dir.x, dir.y, 0.) * 50.,
-            ..t
-        }))
-        .maybe_insert(global_transforms.get(cur).ok().cloned())
-        .maybe_insert(restitutions.get(cur).ok().cloned())
-        .maybe_insert(colliders.get(cur).ok().cloned())
-        .maybe_insert(collider_mass_props.get(cur).ok().cloned())
-        .insert(OnGame);
-}
-
 pub fn handle_stored_items(mut commands: Commands, current: ResMut<Current>) {
     for (i, &e) in current.next.iter().enumerate() {
         commands.add(move |world: &mut World| {
@@ -257,6 +155,7 @@ pub fn handle_stored_items(mut commands: Commands, current: ResMut<Current>) {
     }
 }
 
+/// Queued or held item, which is on the [`CollisionLayer::Held`] layer
 #[derive(Component)]
 pub struct IgnoreCollisions;
 
@@ -265,16 +164,23 @@ pub fn generate_item(
     asset_server: &AssetServer,
     meshes: &mut ResMut<Assets<Mesh>>,
     custom_materials: &mut ResMut<Assets<StickyMaterial>>,
+    items: &ItemAssets,
+    context: &SpawnContext,
     current: &mut Current,
 ) {
     let pos = STORAGE + Vec2::new(0., 75.) * current.next.len() as f32;
     let transform = Transform::from_xyz(pos.x, pos.y, 5.).with_scale(Vec3::ONE * 0.5);
     let entity = random_item(
         &mut current.rng,
+        &mut *current.randomizer,
+        &items.tables().queue,
+        context,
         commands,
         asset_server,
         meshes,
         custom_materials,
+        items,
+        CollisionLayer::Held,
     )
     .insert(GravityScale(0.))
     .insert(TransformBundle::from(transform))
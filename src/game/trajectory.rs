@@ -0,0 +1,265 @@
+//! Predicted path of the throw, which is drawn while the player aims
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    balloon::Balloon,
+    determinism::TIMESTEP,
+    dome::Dome,
+    fields::{field_acceleration, Field},
+    physics::{item_colliders, CollisionLayer},
+    replay::ThrowInput,
+    throw::{throw_impulse, Player},
+    Current, GameClock, OnGame,
+};
+
+/// Seconds of flight the preview looks ahead
+const HORIZON: f32 = 2.;
+const STEPS: usize = (HORIZON / TIMESTEP) as usize;
+/// Integration steps between two dots of the arc
+const DOT_INTERVAL: usize = 3;
+/// Dots of the longest arc, which are spawned once and reused every frame
+const DOTS: usize = STEPS / DOT_INTERVAL + 1;
+const DOT_SIZE: f32 = 8.;
+/// Below the held item
+const PREVIEW_Z: f32 = -1.;
+
+/// Motion of a body that touches nothing
+#[derive(Clone, Copy, Debug)]
+pub struct Flight {
+    pub position: Vec2,
+    pub rotation: f32,
+    pub linvel: Vec2,
+    pub angvel: f32,
+    /// Acceleration everywhere, like gravity
+    pub acceleration: Vec2,
+    pub damping: Damping,
+}
+
+/// Where a body hits something first
+#[derive(Clone, Copy, Debug)]
+pub struct Impact {
+    pub position: Vec2,
+    pub rotation: f32,
+    pub entity: Entity,
+}
+
+#[derive(Clone, Debug)]
+pub struct Trajectory {
+    /// Centre of the body after each step
+    pub points: Vec<Vec2>,
+    pub impact: Option<Impact>,
+}
+
+/// Integrates the flight the way rapier does and sweeps the parts of the body along each step.
+///
+/// Parts are the colliders of the body with their offsets and rotations relative to it. Colliders
+/// can let the body through depending on how it moves, like the dome does. Local acceleration,
+/// like the one from fields, is given the flight and the seconds since it started.
+pub fn predict(
+    rapier_context: &RapierContext,
+    parts: &[(Vec2, f32, Collider)],
+    mut flight: Flight,
+    filter: QueryFilter,
+    passes_through: impl Fn(Entity, &Flight) -> bool,
+    local_acceleration: impl Fn(&Flight, f32) -> Vec2,
+) -> Trajectory {
+    let mut points = vec![flight.position];
+    for step in 0..STEPS {
+        // Velocity is integrated and damped before the position
+        let acceleration =
+            flight.acceleration + local_acceleration(&flight, step as f32 * TIMESTEP);
+        flight.linvel += acceleration * TIMESTEP;
+        flight.linvel *= 1. / (1. + TIMESTEP * flight.damping.linear_damping);
+        flight.angvel *= 1. / (1. + TIMESTEP * flight.damping.angular_damping);
+
+        let hits = |collider| !passes_through(collider, &flight);
+        let filter = filter.predicate(&hits);
+        let hit = parts
+            .iter()
+            .filter_map(|(offset, rotation, collider)| {
+                rapier_context.cast_shape(
+                    flight.position + Vec2::from_angle(flight.rotation).rotate(*offset),
+                    flight.rotation + rotation,
+                    flight.linvel,
+                    collider,
+                    TIMESTEP,
+                    filter,
+                )
+            })
+            .min_by(|(_, a), (_, b)| a.toi.total_cmp(&b.toi));
+        if let Some((entity, toi)) = hit {
+            let position = flight.position + flight.linvel * toi.toi;
+            points.push(position);
+            return Trajectory {
+                points,
+                impact: Some(Impact {
+                    position,
+                    rotation: flight.rotation,
+                    entity,
+                }),
+            };
+        }
+
+        flight.position += flight.linvel * TIMESTEP;
+        flight.rotation += flight.angvel * TIMESTEP;
+        points.push(flight.position);
+    }
+    Trajectory {
+        points,
+        impact: None,
+    }
+}
+
+/// Colliders of the item, either on the body itself or on its children
+pub fn item_parts(
+    entity: Entity,
+    children: Option<&Children>,
+    colliders: &Query<(&Collider, &Transform)>,
+) -> Vec<(Vec2, f32, Collider)> {
+    item_colliders(entity, children)
+        .filter_map(|part| {
+            let (collider, transform) = colliders.get(part).ok()?;
+            let (offset, rotation) = if part == entity {
+                (Vec2::ZERO, 0.)
+            } else {
+                (
+                    transform.translation.xy(),
+                    transform.rotation.to_euler(EulerRot::XYZ).2,
+                )
+            };
+            Some((offset, rotation, collider.clone()))
+        })
+        .collect()
+}
+
+/// Dot of the preview arc, numbered from the held item
+#[derive(Component)]
+pub struct TrajectoryDot(usize);
+
+/// Marks where the preview arc hits something first
+#[derive(Component)]
+pub struct ImpactMarker;
+
+pub fn setup_trajectory_preview(mut commands: Commands, asset_server: Res<AssetServer>) {
+    for i in 0..DOTS {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(DOT_SIZE)),
+                    ..default()
+                },
+                visibility: Visibility::INVISIBLE,
+                ..default()
+            },
+            TrajectoryDot(i),
+            OnGame,
+        ));
+    }
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load("indicator.png"),
+            visibility: Visibility::INVISIBLE,
+            ..default()
+        },
+        ImpactMarker,
+        OnGame,
+    ));
+}
+
+/// Moves the dots and the impact marker along the path the held item would be thrown on
+pub fn handle_trajectory_preview(
+    input: Res<ThrowInput>,
+    current: Res<Current>,
+    rapier_context: Res<RapierContext>,
+    rapier_config: Res<RapierConfiguration>,
+    domes: Query<&GlobalTransform, With<Dome>>,
+    fields: Query<(&Field, &GlobalTransform, Entity)>,
+    clock: Res<GameClock>,
+    players: Query<&Player>,
+    items: Query<(
+        &GlobalTransform,
+        &ReadMassProperties,
+        Option<&Velocity>,
+        Option<&Damping>,
+        Option<&Balloon>,
+        Option<&Children>,
+    )>,
+    colliders: Query<(&Collider, &Transform)>,
+    mut dots: Query<
+        (&TrajectoryDot, &mut Transform, &mut Sprite, &mut Visibility),
+        Without<ImpactMarker>,
+    >,
+    mut markers: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<ImpactMarker>>,
+) {
+    let player = players.single();
+    let trajectory = current.current.filter(|_| input.pressed).and_then(|cur| {
+        let (transform, mass, velocity, damping, balloon, children) = items.get(cur).ok()?;
+        if mass.0.mass <= 0. {
+            return None;
+        }
+        let impulse = throw_impulse(player, input.target());
+        let velocity = velocity.copied().unwrap_or_default();
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        // Thrown items fall with the full gravity, and balloons rise with the lift they get in flight
+        let gravity_scale = balloon.map_or(1., |balloon| balloon.gravity_scale(mass.0.mass));
+        let flight = Flight {
+            position: position.xy(),
+            rotation: rotation.to_euler(EulerRot::XYZ).2,
+            linvel: velocity.linvel + impulse / mass.0.mass,
+            angvel: velocity.angvel,
+            acceleration: rapier_config.gravity * gravity_scale,
+            damping: damping.copied().unwrap_or_default(),
+        };
+        let filter = QueryFilter::new()
+            .groups(CollisionLayer::Item.groups().into())
+            .exclude_sensors()
+            .exclude_rigid_body(cur);
+        let parts = item_parts(cur, children, &colliders);
+        let passes_through = |collider, flight: &Flight| {
+            domes.get(collider).is_ok_and(|dome| {
+                Dome::lets_through(dome.translation().xy(), flight.position, flight.linvel)
+            })
+        };
+        let time = clock.0.elapsed_secs();
+        let local_acceleration = |flight: &Flight, elapsed| {
+            field_acceleration(
+                &rapier_context,
+                &fields,
+                &parts,
+                flight.position,
+                flight.rotation,
+                time + elapsed,
+            )
+        };
+        Some(predict(
+            &rapier_context,
+            &parts,
+            flight,
+            filter,
+            passes_through,
+            local_acceleration,
+        ))
+    });
+
+    let points = trajectory.as_ref().map_or(&[][..], |t| &t.points[..]);
+    for (dot, mut transform, mut sprite, mut visibility) in dots.iter_mut() {
+        let i = dot.0 * DOT_INTERVAL;
+        visibility.is_visible = i < points.len();
+        if let Some(point) = points.get(i) {
+            let fade = 1. - i as f32 / points.len() as f32;
+            transform.translation = point.extend(PREVIEW_Z);
+            sprite.color = Color::rgba(1., 1., 1., 0.8 * fade);
+        }
+    }
+
+    let (mut transform, mut sprite, mut visibility) = markers.single_mut();
+    let impact = trajectory.and_then(|t| t.impact);
+    visibility.is_visible = impact.is_some();
+    if let Some(impact) = impact {
+        let size = (0.1 + 0.9 * player.hold_timer.percent()).powf(2.);
+        sprite.custom_size = Some(Vec2::new(150., 150.) * size);
+        transform.translation = impact.position.extend(PREVIEW_Z);
+    }
+}
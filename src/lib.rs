@@ -0,0 +1,26 @@
+//! Game as a library, so that benchmarks can run the real physics hooks and collision layers
+
+// Bevy systems take their resources as arguments and their queries as long types
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::{audio::AudioSink, prelude::*};
+
+pub mod gallery;
+pub mod game;
+mod menu;
+pub mod splash;
+pub mod utils;
+
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum GameState {
+    Splash,
+    Menu,
+    Game,
+    Gallery,
+}
+
+#[derive(Default, Resource)]
+pub struct Music(Option<Handle<AudioSink>>);
+
+#[derive(Component)]
+pub struct MainCamera;
@@ -1,31 +1,28 @@
-use bevy::audio::AudioSink;
-use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
-use bevy::render::texture::ImageSampler;
-use bevy::{prelude::*, sprite::Material2dPlugin, window::PresentMode};
-use bevy_rapier2d::prelude::*;
-use game::physics::PhysicsData;
-use game::shaders::{StickyMaterial, TilingMaterial};
+use bevy::{prelude::*, sprite::Material2dPlugin};
+use tricky_throws::{
+    gallery,
+    game::{
+        self,
+        determinism::Determinism,
+        physics::PhysicsScale,
+        replay::ReplayMode,
+        sculpture::Gallery,
+        shaders::{StickyMaterial, TilingMaterial},
+    },
+    splash, GameState, MainCamera, Music,
+};
 use wgpu::{AddressMode, SamplerBorderColor, SamplerDescriptor};
 
-mod game;
-mod menu;
-mod splash;
-mod utils;
-
-// TODO: Add one way dome around throwing position
 // TODO: Polish gameplay
 // TODO: Implement sounds
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
-pub enum GameState {
-    Splash,
-    Menu,
-    Game,
-}
-
 fn main() {
+    let replay = ReplayMode::from_args();
     App::new()
         .add_state(GameState::Splash)
+        .insert_resource(Gallery::from_args())
+        .insert_resource(replay.determinism(Determinism::from_args()))
+        .insert_resource(replay)
         .add_plugins(
             DefaultPlugins
                 .set(WindowPlugin {
@@ -42,6 +39,10 @@ fn main() {
                     },
                     ..default()
                 })
+                .set(AssetPlugin {
+                    watch_for_changes: cfg!(feature = "hot_reload"),
+                    ..default()
+                })
                 .set(ImagePlugin {
                     default_sampler: SamplerDescriptor {
                         address_mode_u: AddressMode::ClampToBorder,
@@ -53,10 +54,11 @@ fn main() {
         )
         .add_plugin(splash::SplashPlugin)
         .add_plugin(game::GamePlugin)
+        .add_plugin(gallery::GalleryPlugin)
         // .add_plugin(LogDiagnosticsPlugin::default())
         // .add_plugin(FrameTimeDiagnosticsPlugin::default())
         // .add_plugin(menu::MenuPlugin)
-        .add_plugin(RapierPhysicsPlugin::<PhysicsData>::pixels_per_meter(100.0))
+        .add_plugin(PhysicsScale::default().plugin())
         .add_plugin(Material2dPlugin::<StickyMaterial>::default())
         .add_plugin(Material2dPlugin::<TilingMaterial>::default())
         // .add_plugin(RapierDebugRenderPlugin::default())
@@ -66,11 +68,6 @@ fn main() {
         .run();
 }
 
-#[derive(Default, Resource)]
-pub struct Music(Option<Handle<AudioSink>>);
-
-#[derive(Component)]
-pub struct MainCamera;
 fn setup(mut commands: Commands) {
     commands.init_resource::<Music>();
     commands.spawn(Camera2dBundle::default()).insert(MainCamera);
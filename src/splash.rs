@@ -1,6 +1,13 @@
 use bevy::prelude::*;
 
-use crate::utils::despawn_screen;
+use crate::{
+    game::{
+        items::ItemAssets,
+        level::{CurrentLevel, Level},
+        sculpture::Gallery,
+    },
+    utils::despawn_screen,
+};
 
 use super::GameState;
 
@@ -100,8 +107,21 @@ fn countdown(
     time: Res<Time>,
     buttons: Res<Input<MouseButton>>,
     mut timer: ResMut<SplashTimer>,
+    items: ItemAssets,
+    gallery: Res<Gallery>,
+    (current_level, levels): (Res<CurrentLevel>, Res<Assets<Level>>),
 ) {
+    // Items and the level are spawned right away when the game starts
+    if !items.is_loaded() || !levels.contains(&current_level.0) {
+        timer.tick(time.delta());
+        return;
+    }
     if buttons.just_pressed(MouseButton::Left) || timer.tick(time.delta()).finished() {
-        game_state.set(GameState::Game).unwrap();
+        let state = if gallery.on_start {
+            GameState::Gallery
+        } else {
+            GameState::Game
+        };
+        game_state.set(state).unwrap();
     }
 }